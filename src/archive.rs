@@ -0,0 +1,575 @@
+//! Gzip-compressed certificate files, and tar and zip archives of them.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use flate2::Crc;
+
+use crate::{load_cert_data, CertificateResult, Error, ErrorKind, Origin, Source};
+
+/// The most data read from a compressed file or archive, after decompression.
+///
+/// This guards against "decompression bombs": small files that expand to an
+/// enormous size. Larger files and archives are rejected with
+/// [`ArchiveError::TooLarge`].
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "archive")))]
+pub const MAX_DECOMPRESSED_SIZE: u64 = 64 << 20;
+
+/// Load certificates from each file in the tar or zip archive at `path`.
+///
+/// Tar archives may be gzip-compressed. Each regular file in the archive is read
+/// like a file in a certificate directory; see
+/// [`load_native_certs()`][crate::load_native_certs] for the supported formats.
+/// Directories, links and other special entries are skipped.
+///
+/// Problems with individual files are reported separately, with the path of the
+/// archive joined with the name of the file, and do not stop the others from
+/// being loaded. Certificates have an [`Origin::ArchiveMember`].
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "archive")))]
+pub fn load_certs_from_archive(path: impl AsRef<Path>) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    load_archive(path.as_ref(), &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+pub(crate) fn load_archive(path: &Path, out: &mut CertificateResult) {
+    let data = match fs::metadata(path).and_then(|metadata| match metadata.len() {
+        len if len > MAX_DECOMPRESSED_SIZE => Ok(None),
+        _ => fs::read(path).map(Some),
+    }) {
+        Ok(Some(data)) => data,
+        Ok(None) => return archive_error(out, ArchiveError::TooLarge, path),
+        Err(err) => return out.io_error(err, path, "failed to read archive"),
+    };
+
+    let data = match is_gzip(&data) {
+        true => match gunzip(&data) {
+            Ok(data) => data,
+            Err(error) => return archive_error(out, error, path),
+        },
+        false => data,
+    };
+
+    let mut visit = |name: &str, contents: Result<&[u8], ArchiveError>| {
+        let member_path = path.join(name.trim_start_matches('/'));
+        match contents {
+            Ok(contents) => load_cert_data(
+                contents,
+                &member_path,
+                |index| Origin::ArchiveMember {
+                    path: path.to_owned(),
+                    member: name.to_owned(),
+                    index,
+                },
+                out,
+            ),
+            Err(error) => archive_error(out, error, &member_path),
+        }
+    };
+
+    let result = match data.starts_with(ZIP_LOCAL_HEADER) || data.starts_with(ZIP_END) {
+        true => zip(&data, &mut visit),
+        false => tar(&data, &mut visit),
+    };
+
+    if let Err(error) = result {
+        archive_error(out, error, path);
+    }
+}
+
+pub(crate) fn archive_error(out: &mut CertificateResult, inner: ArchiveError, path: &Path) {
+    out.errors.push(Error {
+        context: "failed to read archive",
+        kind: ErrorKind::Archive {
+            inner,
+            path: path.to_owned(),
+        },
+        from: None,
+    });
+}
+
+pub(crate) fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+}
+
+/// Decompress the gzip data `data`, which may have several members.
+pub(crate) fn gunzip(data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    read_limited(MultiGzDecoder::new(data), MAX_DECOMPRESSED_SIZE)
+}
+
+fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>, ArchiveError> {
+    let mut out = Vec::new();
+    reader
+        .take(limit + 1)
+        .read_to_end(&mut out)
+        .map_err(|_| ArchiveError::Corrupt)?;
+    match out.len() as u64 > limit {
+        true => Err(ArchiveError::TooLarge),
+        false => Ok(out),
+    }
+}
+
+type Visit<'a> = dyn FnMut(&str, Result<&[u8], ArchiveError>) + 'a;
+
+/// Call `visit` for each regular file in the tar archive `data`.
+///
+/// Supports the POSIX ustar and pax formats, and GNU long names.
+fn tar(data: &[u8], visit: &mut Visit<'_>) -> Result<(), ArchiveError> {
+    let mut pos = 0;
+    let mut long_name = None;
+    while let Some(header) = data.get(pos..pos + BLOCK) {
+        if header.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+
+        let checksum = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| match i {
+                148..=155 => u64::from(b' '),
+                _ => u64::from(b),
+            })
+            .sum::<u64>();
+        if number(&header[148..156]) != Some(checksum) {
+            return Err(ArchiveError::Malformed);
+        }
+
+        let start = pos + BLOCK;
+        let contents = number(&header[124..136])
+            .and_then(|size| usize::try_from(size).ok())
+            .and_then(|size| data.get(start..start.checked_add(size)?))
+            .ok_or(ArchiveError::Malformed)?;
+        pos = start + (contents.len() + BLOCK - 1) / BLOCK * BLOCK;
+
+        match header[156] {
+            b'0' | b'\0' | b'7' => {
+                let name = long_name
+                    .take()
+                    .unwrap_or_else(|| header_name(header));
+                visit(&name, Ok(contents));
+            }
+            // GNU long name for the next entry
+            b'L' => long_name = Some(c_string(contents)),
+            // pax extended header for the next entry
+            b'x' => {
+                if let Some(path) = pax_path(contents) {
+                    long_name = Some(path);
+                }
+            }
+            // Directories, links, devices, and global pax headers
+            _ => long_name = None,
+        }
+    }
+
+    // Some writers leave out the end-of-archive blocks.
+    match pos == data.len() {
+        true => Ok(()),
+        false => Err(ArchiveError::Malformed),
+    }
+}
+
+fn header_name(header: &[u8]) -> String {
+    let name = c_string(&header[..100]);
+    let prefix = c_string(&header[345..500]);
+    match header[257..].starts_with(b"ustar") && !prefix.is_empty() {
+        true => format!("{prefix}/{name}"),
+        false => name,
+    }
+}
+
+/// The `path` record of a pax extended header, made up of `<length> <key>=<value>\n` records.
+fn pax_path(mut records: &[u8]) -> Option<String> {
+    while !records.is_empty() {
+        let space = records
+            .iter()
+            .position(|&b| b == b' ')?;
+        let len = std::str::from_utf8(&records[..space])
+            .ok()?
+            .parse::<usize>()
+            .ok()?;
+        let record = records
+            .get(space + 1..len)?
+            .strip_suffix(b"\n")?;
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(path).into_owned());
+        }
+        records = &records[len..];
+    }
+    None
+}
+
+/// A numeric header field: octal digits, or base-256 if the high bit is set.
+fn number(field: &[u8]) -> Option<u64> {
+    if field.first()? & 0x80 != 0 {
+        return field[1..]
+            .iter()
+            .try_fold(0u64, |acc, &b| {
+                acc.checked_mul(256)?
+                    .checked_add(u64::from(b))
+            });
+    }
+
+    let digits = std::str::from_utf8(field)
+        .ok()?
+        .trim_matches(|c| c == ' ' || c == '\0');
+    u64::from_str_radix(digits, 8).ok()
+}
+
+fn c_string(field: &[u8]) -> String {
+    let end = field
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Call `visit` for each regular file in the zip archive `data`.
+///
+/// Files may be stored or deflated. Zip64 archives are not supported.
+fn zip(data: &[u8], visit: &mut Visit<'_>) -> Result<(), ArchiveError> {
+    // The end of central directory record is followed by a comment of up to 64 KiB.
+    let end = (0..=data.len().saturating_sub(ZIP_END_LEN))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&i| data[i..].starts_with(ZIP_END))
+        .ok_or(ArchiveError::Malformed)?;
+    let entries = u16_at(data, end + 10)?;
+    let mut pos = u32_at(data, end + 16)? as usize;
+
+    let mut remaining = MAX_DECOMPRESSED_SIZE;
+    // Entries that share a local header would count the same data more than once.
+    let mut locals = HashSet::new();
+    for _ in 0..entries {
+        if !data
+            .get(pos..)
+            .is_some_and(|header| header.starts_with(ZIP_CENTRAL_HEADER))
+        {
+            return Err(ArchiveError::Malformed);
+        }
+
+        let flags = u16_at(data, pos + 8)?;
+        let method = u16_at(data, pos + 10)?;
+        let crc = u32_at(data, pos + 16)?;
+        let compressed_len = u32_at(data, pos + 20)? as usize;
+        let name_len = u16_at(data, pos + 28)? as usize;
+        let extra_len = u16_at(data, pos + 30)? as usize;
+        let comment_len = u16_at(data, pos + 32)? as usize;
+        let mode = u32_at(data, pos + 38)? >> 16;
+        let local = u32_at(data, pos + 42)? as usize;
+        let name = data
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or(ArchiveError::Malformed)?;
+        let name = String::from_utf8_lossy(name);
+        pos = offset(pos, &[46, name_len, extra_len, comment_len])?;
+
+        // Directories and (Unix) symlinks
+        if name.ends_with('/') || mode & S_IFMT == S_IFLNK {
+            continue;
+        }
+
+        if !locals.insert(local)
+            || !data
+                .get(local..)
+                .is_some_and(|local| local.starts_with(ZIP_LOCAL_HEADER))
+        {
+            return Err(ArchiveError::Malformed);
+        }
+        // The header fields are in bounds, but the lengths they add up to may not be.
+        let start = offset(
+            local,
+            &[
+                30,
+                u16_at(data, local + 26)? as usize,
+                u16_at(data, local + 28)? as usize,
+            ],
+        )?;
+        let compressed = data
+            .get(start..offset(start, &[compressed_len])?)
+            .ok_or(ArchiveError::Malformed)?;
+
+        let contents = match (flags & ENCRYPTED, method) {
+            (0, STORED) if compressed.len() as u64 > remaining => Err(ArchiveError::TooLarge),
+            (0, STORED) => Ok(compressed.to_vec()),
+            (0, DEFLATED) => read_limited(DeflateDecoder::new(compressed), remaining),
+            (0, method) => Err(ArchiveError::UnsupportedCompression(method)),
+            _ => Err(ArchiveError::Encrypted),
+        }
+        .and_then(|contents| {
+            let mut actual = Crc::new();
+            actual.update(&contents);
+            match actual.sum() == crc {
+                true => Ok(contents),
+                false => Err(ArchiveError::Corrupt),
+            }
+        });
+
+        match contents {
+            Ok(contents) => {
+                remaining = remaining.saturating_sub(contents.len() as u64);
+                visit(&name, Ok(&contents));
+            }
+            // Stop at the limit for the whole archive.
+            Err(ArchiveError::TooLarge) => return Err(ArchiveError::TooLarge),
+            Err(error) => visit(&name, Err(error)),
+        }
+    }
+
+    Ok(())
+}
+
+/// `base` plus each of `lengths`, or an error if that overflows.
+fn offset(base: usize, lengths: &[usize]) -> Result<usize, ArchiveError> {
+    lengths
+        .iter()
+        .try_fold(base, |acc, &len| acc.checked_add(len))
+        .ok_or(ArchiveError::Malformed)
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16, ArchiveError> {
+    data.get(pos..offset(pos, &[2])?)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or(ArchiveError::Malformed)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32, ArchiveError> {
+    data.get(pos..offset(pos, &[4])?)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(ArchiveError::Malformed)
+}
+
+/// Why a compressed file or archive, or a file in an archive, could not be read.
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "archive")))]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// The archive is not a valid tar or zip archive.
+    Malformed,
+    /// The compressed data is corrupt, or does not match its checksum.
+    Corrupt,
+    /// The data is larger than [`MAX_DECOMPRESSED_SIZE`] after decompression.
+    TooLarge,
+    /// The file is compressed with an unsupported method. Contains the zip
+    /// compression method.
+    UnsupportedCompression(u16),
+    /// The file is encrypted.
+    Encrypted,
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => f.write_str("malformed archive"),
+            Self::Corrupt => f.write_str("corrupt compressed data"),
+            Self::TooLarge => write!(
+                f,
+                "decompressed data larger than {MAX_DECOMPRESSED_SIZE} bytes"
+            ),
+            Self::UnsupportedCompression(method) => {
+                write!(f, "unsupported zip compression method {method}")
+            }
+            Self::Encrypted => f.write_str("encrypted archive member"),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+const BLOCK: usize = 512;
+
+const ZIP_LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER: &[u8] = b"PK\x01\x02";
+const ZIP_END: &[u8] = b"PK\x05\x06";
+const ZIP_END_LEN: usize = 22;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+const ENCRYPTED: u16 = 1;
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFLNK: u32 = 0o120_000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn archives() {
+        for archive in ["tests/certs.tar", "tests/certs.tar.gz", "tests/certs.zip"] {
+            let result = load_certs_from_archive(archive);
+
+            // The fixtures contain a valid bundle, a directory, a symlink, and a
+            // file that isn't a certificate.
+            let [Error { kind, .. }] = &result.errors[..] else {
+                panic!("unexpected errors {:?}", result.errors);
+            };
+            assert!(
+                matches!(kind, ErrorKind::UnrecognizedFormat { path } if path == &Path::new(archive).join("certs/junk.der")),
+                "{kind:?}"
+            );
+
+            assert_eq!(result.certs.len(), 2, "{archive}");
+            let members = result
+                .provenance()
+                .map(|(_, provenance)| match &provenance.origin {
+                    Origin::ArchiveMember {
+                        path,
+                        member,
+                        index,
+                    } => {
+                        assert_eq!(path, Path::new(archive));
+                        (member.as_str(), *index)
+                    }
+                    origin => panic!("unexpected origin {origin:?}"),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(members, [("certs/bundle.pem", 0), ("certs/bundle.pem", 1)]);
+        }
+    }
+
+    #[test]
+    fn gzip_bomb() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        let zeros = vec![0; 1 << 20];
+        for _ in 0..=MAX_DECOMPRESSED_SIZE >> 20 {
+            encoder.write_all(&zeros).unwrap();
+        }
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < 1 << 20);
+        assert_eq!(gunzip(&bomb), Err(ArchiveError::TooLarge));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("bomb.tar.gz");
+        fs::write(&path, bomb).unwrap();
+        let result = load_certs_from_archive(&path);
+        assert!(matches!(
+            result.errors[..],
+            [Error {
+                kind: ErrorKind::Archive {
+                    inner: ArchiveError::TooLarge,
+                    ..
+                },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn malformed() {
+        let mut visit = |_: &str, _: Result<&[u8], ArchiveError>| {};
+
+        let tar_data = fs::read("tests/certs.tar").unwrap();
+        assert_eq!(
+            tar(&tar_data[..600], &mut visit),
+            Err(ArchiveError::Malformed)
+        );
+        let mut bad_checksum = tar_data.clone();
+        bad_checksum[0] ^= 1;
+        assert_eq!(tar(&bad_checksum, &mut visit), Err(ArchiveError::Malformed));
+
+        let zip_data = fs::read("tests/certs.zip").unwrap();
+        assert_eq!(
+            zip(&zip_data[..100], &mut visit),
+            Err(ArchiveError::Malformed)
+        );
+
+        // A corrupt member is reported, and the others still read.
+        let mut corrupt = zip_data.clone();
+        let offset = corrupt
+            .windows(4)
+            .position(|w| w == b"-BEG")
+            .unwrap();
+        corrupt[offset] = b'+';
+        let mut visited = Vec::new();
+        let mut visit = |name: &str, contents: Result<&[u8], ArchiveError>| {
+            visited.push((name.to_owned(), contents.map(<[u8]>::to_vec)))
+        };
+        assert_eq!(zip(&corrupt, &mut visit), Ok(()));
+        assert!(visited
+            .iter()
+            .any(|(_, contents)| contents == &Err(ArchiveError::Corrupt)));
+        assert!(visited
+            .iter()
+            .any(|(_, contents)| contents.is_ok()));
+
+        assert_eq!(gunzip(&[0x1f, 0x8b, 0]), Err(ArchiveError::Corrupt));
+    }
+
+    #[test]
+    fn zip_overlapping_members() {
+        let mut visit = |_: &str, _: Result<&[u8], ArchiveError>| {};
+
+        // Two entries for the same stored member.
+        let mut data = local_header();
+        data.extend_from_slice(b"hello");
+        let twice = zip_with(data, &[(0, 5), (0, 5)]);
+        assert_eq!(zip(&twice, &mut visit), Err(ArchiveError::Malformed));
+
+        // Stored members whose data overlaps, adding up to more than the limit.
+        let members = (MAX_DECOMPRESSED_SIZE >> 20) as usize + 1;
+        let mut data = Vec::new();
+        for _ in 0..members {
+            data.extend_from_slice(&local_header());
+        }
+        data.resize(data.len() + (1 << 20), 0);
+        let entries = (0..members)
+            .map(|i| (30 * i, data.len() - 30 * (i + 1)))
+            .collect::<Vec<_>>();
+        let overlapping = zip_with(data, &entries);
+        assert_eq!(zip(&overlapping, &mut visit), Err(ArchiveError::TooLarge));
+    }
+
+    /// A local file header for a stored member without a name.
+    fn local_header() -> Vec<u8> {
+        let mut header = ZIP_LOCAL_HEADER.to_vec();
+        header.resize(30, 0);
+        header
+    }
+
+    /// `data` followed by a central directory with an unnamed stored member for each
+    /// `(local header offset, length)` in `entries`.
+    fn zip_with(mut data: Vec<u8>, entries: &[(usize, usize)]) -> Vec<u8> {
+        let directory = data.len();
+        for &(local, len) in entries {
+            let mut crc = Crc::new();
+            crc.update(&data[local + 30..local + 30 + len]);
+            let mut header = ZIP_CENTRAL_HEADER.to_vec();
+            header.resize(46, 0);
+            header[16..20].copy_from_slice(&crc.sum().to_le_bytes());
+            header[20..24].copy_from_slice(&(len as u32).to_le_bytes());
+            header[42..46].copy_from_slice(&(local as u32).to_le_bytes());
+            data.extend_from_slice(&header);
+        }
+
+        let mut end = ZIP_END.to_vec();
+        end.resize(ZIP_END_LEN, 0);
+        end[10..12].copy_from_slice(&(entries.len() as u16).to_le_bytes());
+        end[16..20].copy_from_slice(&(directory as u32).to_le_bytes());
+        data.extend_from_slice(&end);
+        data
+    }
+
+    #[test]
+    fn pax_and_long_names() {
+        assert_eq!(
+            pax_path(b"20 mtime=1700000000\n28 path=certs/long-name.pem\n"),
+            Some("certs/long-name.pem".into())
+        );
+        assert_eq!(pax_path(b"12 uid=1000\n"), None);
+        assert_eq!(pax_path(b"99 path=x\n"), None);
+        assert_eq!(number(b"0000644\0"), Some(0o644));
+        assert_eq!(number(&[0x80, 0, 0, 1, 0]), Some(256));
+        assert_eq!(number(b"12x\0"), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! PEM sections with labels that `rustls-pki-types` does not know about, and
+//! writing PEM.
+
+/// Iterate over the PEM sections in `data` labelled `label`.
+///
+/// Yields the decoded contents of each section, or `Err(())` if a section's
+/// contents are not valid base64. Sections without an end marker are ignored.
+pub(crate) fn sections<'a>(
+    data: &'a [u8],
+    label: &str,
+) -> impl Iterator<Item = Result<Vec<u8>, ()>> + 'a {
+    sections_with_offsets(data, label).map(|(_, section)| section)
+}
+
+/// Like [`sections()`], but also yields the offset in `data` just past the end
+/// marker of each section.
+pub(crate) fn sections_with_offsets<'a>(
+    data: &'a [u8],
+    label: &str,
+) -> impl Iterator<Item = (usize, Result<Vec<u8>, ()>)> + 'a {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let mut offset = 0;
+    let mut lines = data
+        .split(|&b| b == b'\n')
+        .map(move |line| {
+            offset += line.len() + 1;
+            (offset.min(data.len()), line)
+        });
+    std::iter::from_fn(move || {
+        lines.find(|(_, line)| trim(line) == begin.as_bytes())?;
+
+        let mut base64 = Vec::new();
+        for (offset, line) in lines.by_ref() {
+            let line = trim(line);
+            if line == end.as_bytes() {
+                return Some((offset, decode_base64(&base64)));
+            }
+            base64.extend_from_slice(line);
+        }
+
+        None
+    })
+}
+
+fn trim(line: &[u8]) -> &[u8] {
+    let end = line
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(0, |i| i + 1);
+    &line[..end]
+}
+
+/// Encode `der` as a PEM section labelled `label`, with 64-character lines.
+pub(crate) fn encode(label: &str, der: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut base64 = String::with_capacity((der.len() + 2) / 3 * 4);
+    for chunk in der.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => base64.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f])),
+                false => base64.push('='),
+            }
+        }
+    }
+
+    let mut out = format!("-----BEGIN {label}-----\n");
+    for line in base64.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    out.push_str(&format!("-----END {label}-----\n"));
+    out
+}
+
+pub(crate) fn decode_base64(input: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    let (mut acc, mut bits, mut padding) = (0u32, 0u32, 0usize);
+    for &b in input {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            b if b.is_ascii_whitespace() => continue,
+            _ => return Err(()),
+        };
+
+        // No data may follow padding.
+        if padding > 0 {
+            return Err(());
+        }
+
+        acc = ((acc << 6) | u32::from(value)) & 0xfff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+
+    match (bits, padding) {
+        (0, 0) | (4, 2) | (2, 1) => Ok(out),
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64() {
+        assert_eq!(decode_base64(b"").unwrap(), b"");
+        assert_eq!(decode_base64(b"Zm9v").unwrap(), b"foo");
+        assert_eq!(decode_base64(b"Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(decode_base64(b"Zm9v\r\nYmE=").unwrap(), b"fooba");
+        assert!(decode_base64(b"Zm9vY").is_err());
+        assert!(decode_base64(b"Zm9vYg=").is_err());
+        assert!(decode_base64(b"Zg==Zg==").is_err());
+        assert!(decode_base64(b"Zm9v!").is_err());
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(
+            encode("FOO", b"fooba"),
+            "-----BEGIN FOO-----\nZm9vYmE=\n-----END FOO-----\n"
+        );
+        assert_eq!(encode("FOO", b"fo").lines().nth(1), Some("Zm8="));
+
+        let data = (0..=255).collect::<Vec<u8>>();
+        let encoded = encode("BAR", &data);
+        assert!(encoded
+            .lines()
+            .all(|line| line.len() <= 64));
+        assert_eq!(
+            sections(encoded.as_bytes(), "BAR").collect::<Vec<_>>(),
+            [Ok(data)]
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let data = b"junk\n-----BEGIN FOO-----\r\nZm9v\r\n-----END FOO-----\r\n\
+                     -----BEGIN BAR-----\nYmFy\n-----END BAR-----\n\
+                     -----BEGIN FOO-----\n!!!!\n-----END FOO-----\n\
+                     -----BEGIN FOO-----\nZm9v\n";
+        let found = sections_with_offsets(data, "FOO").collect::<Vec<_>>();
+        assert_eq!(found, vec![(51, Ok(b"foo".to_vec())), (137, Err(()))]);
+    }
+}
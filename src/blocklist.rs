@@ -0,0 +1,396 @@
+//! Distrusting specific roots, wherever they are loaded from.
+//!
+//! A blocklist file has one rule per line. Blank lines and lines starting with
+//! `#` are ignored:
+//!
+//! ```text
+//! # Example CA, distrusted 2024-06-01
+//! sha256:55926084ec963a64b96e2abe01ce0ba86a64fbfebcc7aab5afc155b37fd76066
+//! spki-sha256:B0:F3:30:A3:1A:0C:50:98:7E:1C:3A:7B:B0:2C:2D:DA:68:29:91:D3:16:5B:51:7B:D4:4F:BA:4A:60:20:BD:94
+//! subject:CN=Example Root CA,O=Example,C=US
+//! ```
+
+use std::path::Path;
+use std::{fmt, fs};
+
+use pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+
+use crate::{info, x509, CertificateResult, Error, ErrorKind, ExclusionReason};
+
+/// A list of roots that must not be trusted, however they were loaded.
+///
+/// Certificates matching any rule are moved from [`CertificateResult::certs`] to
+/// [`CertificateResult::excluded`] as [`ExclusionReason::Blocked`], which records
+/// the rule that matched.
+///
+/// [`load_native_certs()`][crate::load_native_certs] and
+/// [`load_certs_from_paths()`][crate::load_certs_from_paths] apply the blocklist
+/// file named by the `RUSTLS_NATIVE_CERTS_BLOCKLIST` environment variable. A
+/// [`Loader`][crate::Loader] applies it if [`Loader::env()`][crate::Loader::env] is
+/// enabled, along with any given to [`Loader::blocklist()`][crate::Loader::blocklist].
+/// Each line of that file that can't be parsed is reported as an
+/// [`ErrorKind::MalformedBlocklist`] error, and the other rules still apply.
+///
+/// ```no_run
+/// use rustls_native_certs::{Blocklist, BlockRule, Loader};
+///
+/// let blocklist = Blocklist::from_file("/etc/rustls/blocklist")
+///     .unwrap()
+///     .rule(BlockRule::Subject("CN=Example Root CA,O=Example,C=US".into()));
+/// let result = Loader::new()
+///     .platform(true)
+///     .blocklist(blocklist)
+///     .load();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Blocklist {
+    rules: Vec<BlockRule>,
+}
+
+impl Blocklist {
+    /// Create an empty blocklist.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a blocklist file, in the format described in [`BlockRule`].
+    ///
+    /// Fails if any line can't be parsed.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let (blocklist, mut malformed) = Self::read(path.as_ref())?;
+        match malformed.is_empty() {
+            true => Ok(blocklist),
+            false => Err(malformed.remove(0)),
+        }
+    }
+
+    /// Read a blocklist file, keeping the rules that can be parsed, along with an
+    /// error for each line that can't.
+    fn read(path: &Path) -> Result<(Self, Vec<Error>), Error> {
+        let data = fs::read_to_string(path).map_err(|err| Error {
+            context: "failed to read blocklist",
+            kind: ErrorKind::Io {
+                inner: err,
+                path: path.to_owned(),
+            },
+            from: None,
+        })?;
+
+        let (mut rules, mut malformed) = (Vec::new(), Vec::new());
+        for (i, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match BlockRule::parse(line) {
+                Some(rule) => rules.push(rule),
+                None => malformed.push(Error {
+                    context: "failed to read blocklist",
+                    kind: ErrorKind::MalformedBlocklist {
+                        path: path.to_owned(),
+                        line: i + 1,
+                    },
+                    from: None,
+                }),
+            }
+        }
+
+        Ok((Self { rules }, malformed))
+    }
+
+    /// Add `rule` to the blocklist.
+    pub fn rule(mut self, rule: BlockRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// The rules in the blocklist, in order.
+    pub fn rules(&self) -> &[BlockRule] {
+        &self.rules
+    }
+
+    /// The first rule that matches `cert`, if any.
+    pub fn matches(&self, cert: &CertificateDer<'_>) -> Option<&BlockRule> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        let fingerprint: [u8; 32] = Sha256::digest(cert).into();
+        let spki = x509::subject_public_key_info(cert)
+            .ok()
+            .map(|spki| <[u8; 32]>::from(Sha256::digest(spki)));
+        let subject = x509::subject(cert)
+            .ok()
+            .and_then(|name| info::format_name(name).ok())
+            .map(|name| normalize_name(&name));
+
+        self.rules
+            .iter()
+            .find(|rule| match rule {
+                BlockRule::Sha256(hash) => *hash == fingerprint,
+                BlockRule::SpkiSha256(hash) => Some(*hash) == spki,
+                BlockRule::Subject(name) => subject.as_deref() == Some(&normalize_name(name)),
+            })
+    }
+
+    pub(crate) fn extend(&mut self, other: &Self) {
+        self.rules
+            .extend_from_slice(&other.rules);
+    }
+
+    /// Exclude the certificates in `out` that match a rule.
+    fn apply(&self, out: &mut CertificateResult) {
+        if self.rules.is_empty() {
+            return;
+        }
+
+        out.exclude_where(|cert| {
+            self.matches(cert)
+                .map(|rule| ExclusionReason::Blocked { rule: rule.clone() })
+        });
+    }
+}
+
+/// A rule that blocks a root.
+///
+/// In a blocklist file, and when formatted with `Display`, a rule is written as
+/// `sha256:<hex>`, `spki-sha256:<hex>` or `subject:<name>`. Hex digits may be in
+/// either case, and separated by colons as `openssl x509 -fingerprint` prints them.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlockRule {
+    /// The SHA-256 fingerprint of the whole certificate.
+    Sha256([u8; 32]),
+    /// The SHA-256 hash of the certificate's DER-encoded `SubjectPublicKeyInfo`,
+    /// which also matches other certificates for the same key.
+    SpkiSha256([u8; 32]),
+    /// The certificate's subject, as an RFC 4514 string like those of
+    /// [`CertificateInfo::subject`][crate::CertificateInfo::subject] or
+    /// `openssl x509 -subject -nameopt RFC2253`.
+    ///
+    /// Names are compared ignoring ASCII case and spaces around `,`, `+` and `=`.
+    Subject(String),
+}
+
+impl BlockRule {
+    fn parse(line: &str) -> Option<Self> {
+        let (kind, value) = line.split_once(':')?;
+        let value = value.trim();
+        match kind.trim() {
+            "sha256" => Some(Self::Sha256(parse_hash(value)?)),
+            "spki-sha256" => Some(Self::SpkiSha256(parse_hash(value)?)),
+            "subject" if !value.is_empty() => Some(Self::Subject(value.to_owned())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BlockRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sha256(hash) => write!(f, "sha256:{}", info::hex(hash)),
+            Self::SpkiSha256(hash) => write!(f, "spki-sha256:{}", info::hex(hash)),
+            Self::Subject(name) => write!(f, "subject:{name}"),
+        }
+    }
+}
+
+/// Apply `blocklist` to `out`, along with the blocklist file at `path`, if any, as
+/// named by `RUSTLS_NATIVE_CERTS_BLOCKLIST`.
+///
+/// Errors reading the file are added to `out`, as is an
+/// [`ErrorKind::MalformedBlocklist`] error for each line that can't be parsed; the
+/// other rules still apply.
+pub(crate) fn apply_with_env(
+    blocklist: &Blocklist,
+    path: Option<&Path>,
+    out: &mut CertificateResult,
+) {
+    let mut combined = blocklist.clone();
+    let mut errors = CertificateResult::default();
+    if let Some(path) = path {
+        match Blocklist::read(path) {
+            Ok((from_env, malformed)) => {
+                combined.extend(&from_env);
+                errors.errors = malformed;
+            }
+            Err(err) => errors.errors.push(err),
+        }
+    }
+
+    combined.apply(out);
+    out.extend(errors, Some(crate::Source::Environment));
+}
+
+/// Apply the blocklist file at `path`, if any, to `out`; see [`apply_with_env()`].
+pub(crate) fn apply_env(path: Option<&Path>, out: &mut CertificateResult) {
+    apply_with_env(&Blocklist::new(), path, out);
+}
+
+fn parse_hash(value: &str) -> Option<[u8; 32]> {
+    let digits = value
+        .bytes()
+        .filter(|&b| b != b':')
+        .collect::<Vec<_>>();
+    if digits.len() != 64 {
+        return None;
+    }
+
+    let mut hash = [0; 32];
+    for (byte, pair) in hash.iter_mut().zip(digits.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// Lowercase the RFC 4514 string `name`, and remove spaces around its `,`, `+` and
+/// `=` separators.
+fn normalize_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped.to_ascii_lowercase());
+                }
+            }
+            ',' | '+' | '=' => {
+                while out.ends_with(' ') && !out.ends_with("\\ ") {
+                    out.pop();
+                }
+                out.push(c);
+                chars = chars
+                    .as_str()
+                    .trim_start_matches(' ')
+                    .chars();
+            }
+            c => out.push(c.to_ascii_lowercase()),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+    use pki_types::pem::PemObject;
+
+    #[test]
+    fn rules() {
+        let cert = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        let other =
+            CertificateDer::from_pem_slice(include_bytes!("../tests/code-signing-ca.pem")).unwrap();
+        let fingerprint = Sha256::digest(&cert).into();
+        let spki = Sha256::digest(x509::subject_public_key_info(&cert).unwrap()).into();
+
+        for rule in [
+            BlockRule::Sha256(fingerprint),
+            BlockRule::SpkiSha256(spki),
+            BlockRule::Subject("CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3".into()),
+            BlockRule::Subject("cn = globalsign, o=GlobalSign , OU=GlobalSign Root CA - R3".into()),
+        ] {
+            let blocklist = Blocklist::new().rule(rule.clone());
+            assert_eq!(blocklist.matches(&cert), Some(&rule));
+            assert_eq!(blocklist.matches(&other), None);
+        }
+
+        assert_eq!(
+            Blocklist::new()
+                .rule(BlockRule::Subject("CN=GlobalSign".into()))
+                .matches(&cert),
+            None
+        );
+        assert_eq!(Blocklist::new().matches(&cert), None);
+    }
+
+    #[test]
+    fn apply_without_provenance() {
+        let cert = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        let mut result = CertificateResult::default();
+        result.certs.push(cert.clone());
+
+        Blocklist::new()
+            .rule(BlockRule::Sha256(Sha256::digest(&cert).into()))
+            .apply(&mut result);
+        assert!(result.certs.is_empty());
+        assert_eq!(result.excluded[0].cert, cert);
+        assert_eq!(result.excluded[0].origin, crate::Origin::Unknown);
+    }
+
+    #[test]
+    fn parse() {
+        let hex = "55926084ec963a64b96e2abe01ce0ba86a64fbfebcc7aab5afc155b37fd76066";
+        let rule = BlockRule::parse(&format!("sha256:{hex}")).unwrap();
+        assert_eq!(rule.to_string(), format!("sha256:{hex}"));
+
+        let colons = hex
+            .to_uppercase()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(
+            BlockRule::parse(&format!("spki-sha256: {colons}")),
+            match rule {
+                BlockRule::Sha256(hash) => Some(BlockRule::SpkiSha256(hash)),
+                _ => unreachable!(),
+            }
+        );
+        assert_eq!(
+            BlockRule::parse("subject:CN=a\\, b,O=c"),
+            Some(BlockRule::Subject("CN=a\\, b,O=c".into()))
+        );
+
+        assert_eq!(BlockRule::parse("sha256:0011"), None);
+        assert_eq!(BlockRule::parse("sha1:00"), None);
+        assert_eq!(BlockRule::parse("subject:"), None);
+        assert_eq!(BlockRule::parse(hex), None);
+
+        assert_eq!(normalize_name(" CN = A\\ ,O=b "), "cn=a\\ ,o=b");
+    }
+
+    #[test]
+    fn from_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("blocklist");
+        fs::write(
+            &path,
+            "# comment\n\nsubject:CN=a\n  spki-sha256:0000000000000000000000000000000000000000000000000000000000000000\n",
+        )
+        .unwrap();
+        let blocklist = Blocklist::from_file(&path).unwrap();
+        assert_eq!(
+            blocklist.rules(),
+            [
+                BlockRule::Subject("CN=a".into()),
+                BlockRule::SpkiSha256([0; 32])
+            ]
+        );
+
+        fs::write(&path, "subject:CN=a\nCN=b\n").unwrap();
+        let err = Blocklist::from_file(&path).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MalformedBlocklist { line: 2, .. }
+        ));
+
+        let err = Blocklist::from_file(temp_dir.path().join("missing")).unwrap_err();
+        assert!(
+            matches!(err.kind, ErrorKind::Io { ref inner, .. } if inner.kind() == io::ErrorKind::NotFound)
+        );
+    }
+}
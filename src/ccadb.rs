@@ -0,0 +1,361 @@
+//! Trust anchors from CSV reports of the [Common CA Database](https://www.ccadb.org/resources).
+//!
+//! Reports such as "Included CA Certificate PEM" list one root per record, with
+//! the root program's trust bits, EV policy OIDs, distrust dates, and the
+//! certificate itself as PEM. Columns are found by name in the header, so the
+//! order of columns and any columns not used here do not matter.
+
+use std::fs;
+use std::path::Path;
+
+use pki_types::CertificateDer;
+
+use crate::{
+    armor, x509, CertificateResult, Error, ErrorKind, ExclusionReason, Origin, RootMetadata, Source,
+};
+
+/// Load the roots with the "Websites" trust bit in `program` from the CCADB CSV
+/// report at `path`.
+///
+/// The report must have a column with the certificate as PEM (`PEM Info` or
+/// `PEM`) and the trust bits for `program`; see [`RootProgram`]. Roots without
+/// the "Websites" (or "Server Authentication") trust bit are listed in
+/// [`CertificateResult::excluded`] as [`ExclusionReason::NotTrustedForServerAuth`],
+/// and those with a "Distrust for TLS After Date" as
+/// [`ExclusionReason::ServerDistrustAfter`].
+///
+/// The metadata for each root is available from its [`Origin::RootList`].
+pub fn load_certs_from_ccadb(path: impl AsRef<Path>, program: RootProgram) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    load_ccadb(path.as_ref(), program, &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+/// A root program, whose trust bits are used to select roots from a CCADB report.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootProgram {
+    /// Mozilla's root store, from the `Trust Bits` or `Mozilla Trust Bits` column.
+    Mozilla,
+    /// Microsoft's root store, from the `Microsoft EKUs` or `Microsoft Trust Bits` column.
+    Microsoft,
+    /// The Chrome Root Store, from the `Chrome Trust Bits` column.
+    Chrome,
+    /// Apple's root store, from the `Apple Trust Bits` column.
+    Apple,
+}
+
+impl RootProgram {
+    /// The names of the column holding this program's trust bits, in order of preference.
+    fn trust_bit_columns(self) -> &'static [&'static str] {
+        match self {
+            Self::Mozilla => &["Trust Bits", "Mozilla Trust Bits"],
+            Self::Microsoft => &["Microsoft EKUs", "Microsoft Trust Bits"],
+            Self::Chrome => &["Chrome Trust Bits"],
+            Self::Apple => &["Apple Trust Bits"],
+        }
+    }
+}
+
+pub(crate) fn load_ccadb(path: &Path, program: RootProgram, out: &mut CertificateResult) {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            out.io_error(err, path, "failed to read CCADB report");
+            return;
+        }
+    };
+
+    let malformed = |out: &mut CertificateResult, line| {
+        out.errors.push(Error {
+            context: "failed to read CCADB report",
+            kind: ErrorKind::MalformedCcadb {
+                path: path.to_owned(),
+                line,
+            },
+            from: None,
+        });
+    };
+
+    let text = String::from_utf8_lossy(&data);
+    let mut records = match records(text.trim_start_matches('\u{feff}')) {
+        Ok(records) => records.into_iter(),
+        Err(line) => return malformed(out, line),
+    };
+
+    let Some((_, header)) = records.next() else {
+        return;
+    };
+    let column = |names: &[&str]| {
+        names.iter().find_map(|name| {
+            header
+                .iter()
+                .position(|h| h.trim() == *name)
+        })
+    };
+    let (Some(pem), Some(trust_bits)) = (
+        column(&["PEM Info", "PEM"]),
+        column(program.trust_bit_columns()),
+    ) else {
+        return malformed(out, 1);
+    };
+    let owner = column(&["Owner", "CA Owner"]);
+    let name = column(&[
+        "Common Name or Certificate Name",
+        "CA Common Name or Certificate Name",
+        "Certificate Name",
+    ]);
+    let fingerprint = column(&["SHA-256 Fingerprint"]);
+    let ev_policy_oids = column(&["EV Policy OID(s)", "EV Policy OIDs"]);
+    let distrust_after = column(&["Distrust for TLS After Date"]);
+
+    for (index, (line, record)) in records.enumerate() {
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|i| record.get(i))
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+
+        let pem = field(Some(pem))
+            .unwrap_or_default()
+            .trim_matches('\'');
+        let cert = match armor::sections(pem.as_bytes(), "CERTIFICATE").next() {
+            Some(Ok(der)) => CertificateDer::from(der),
+            _ => {
+                malformed(out, line);
+                continue;
+            }
+        };
+
+        let tls_distrust_after = match field(distrust_after) {
+            None => None,
+            Some(date) => match parse_date(date) {
+                Some(time) => Some(time),
+                None => {
+                    malformed(out, line);
+                    continue;
+                }
+            },
+        };
+
+        let metadata = RootMetadata {
+            name: field(name).map(str::to_owned),
+            owner: field(owner).map(str::to_owned),
+            sha256_fingerprint: field(fingerprint).map(str::to_owned),
+            trust_bits: list(field(Some(trust_bits))),
+            ev_policy_oids: list(field(ev_policy_oids))
+                .into_iter()
+                .filter(|oid| {
+                    oid.bytes()
+                        .all(|b| b.is_ascii_digit() || b == b'.')
+                })
+                .collect(),
+            tls_distrust_after,
+            constraints: Vec::new(),
+        };
+
+        let reason = match (metadata.trusted_for_websites(), tls_distrust_after) {
+            (false, _) => Some(ExclusionReason::NotTrustedForServerAuth),
+            (true, Some(time)) => Some(ExclusionReason::ServerDistrustAfter { time }),
+            (true, None) => None,
+        };
+
+        let origin = Origin::RootList {
+            path: path.to_owned(),
+            index,
+            metadata: Box::new(metadata),
+        };
+        match reason {
+            Some(reason) => out.exclude(cert, origin, reason),
+            None => out.push_cert(cert, origin),
+        }
+    }
+}
+
+impl RootMetadata {
+    fn trusted_for_websites(&self) -> bool {
+        self.trust_bits
+            .iter()
+            .any(|bit| bit == "Websites" || bit == "Server Authentication")
+    }
+}
+
+/// Split a `;`- or `,`-separated list, as used for trust bits and policy OIDs.
+fn list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split([';', ','])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parse a `YYYY.MM.DD` or `YYYY-MM-DD` date, as the start of that day in UTC.
+fn parse_date(value: &str) -> Option<std::time::SystemTime> {
+    let mut parts = value.split(['.', '-']);
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    x509::parse_time(format!("{year}{month}{day}000000Z").as_bytes())
+}
+
+/// Split CSV `text`, as described in [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180),
+/// into records and their fields.
+///
+/// Each record is returned with the line it starts on, starting at 1. On error,
+/// returns the line of the record that could not be parsed.
+fn records(text: &str) -> Result<Vec<(usize, Vec<String>)>, usize> {
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            match (chars.next(), quoted) {
+                (Some('"'), true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (Some('"'), true) => match chars.peek() {
+                    Some(',' | '\r' | '\n') | None => quoted = false,
+                    Some(_) => return Err(start),
+                },
+                (Some('"'), false) if field.is_empty() => quoted = true,
+                (Some(','), false) => fields.push(std::mem::take(&mut field)),
+                (Some('\r'), false) if chars.peek() == Some(&'\n') => {}
+                (Some('\n'), false) | (None, false) => {
+                    line += 1;
+                    fields.push(field);
+                    break;
+                }
+                (Some(c), _) => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+                (None, true) => return Err(start),
+            }
+        }
+
+        // Skip blank lines.
+        if fields.len() > 1 || !fields[0].is_empty() {
+            records.push((start, fields));
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pki_types::pem::PemObject;
+
+    #[test]
+    fn trust_bits() {
+        let path = Path::new("tests/ccadb.csv");
+        let result = load_certs_from_ccadb(path, RootProgram::Mozilla);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(
+            result.certs,
+            [
+                CertificateDer::from_pem_slice(include_bytes!("../tests/badssl-com-chain.pem"))
+                    .unwrap()
+            ]
+        );
+
+        let (_, provenance) = result.provenance().next().unwrap();
+        let Origin::RootList {
+            path: p,
+            index,
+            metadata,
+        } = &provenance.origin
+        else {
+            panic!("unexpected origin {:?}", provenance.origin);
+        };
+        assert_eq!((p.as_path(), *index), (path, 0));
+        assert_eq!(metadata.name.as_deref(), Some("*.badssl.com"));
+        assert_eq!(metadata.owner.as_deref(), Some("BadSSL"));
+        assert_eq!(metadata.trust_bits, ["Email", "Websites"]);
+        assert_eq!(metadata.ev_policy_oids, ["2.23.140.1.1"]);
+        assert_eq!(metadata.tls_distrust_after, None);
+
+        let reasons = result
+            .excluded
+            .iter()
+            .map(|exclusion| match &exclusion.origin {
+                Origin::RootList { index, .. } => (*index, exclusion.reason.clone()),
+                origin => panic!("unexpected origin {origin:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            [
+                (1, ExclusionReason::NotTrustedForServerAuth),
+                (
+                    2,
+                    ExclusionReason::ServerDistrustAfter {
+                        time: x509::parse_time(b"20241130000000Z").unwrap()
+                    }
+                ),
+            ]
+        );
+
+        // Another root program's trust bits are used if asked for.
+        let result = load_certs_from_ccadb(path, RootProgram::Microsoft);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 1);
+        assert_eq!(result.excluded.len(), 2);
+
+        // ... but must be present.
+        let result = load_certs_from_ccadb(path, RootProgram::Apple);
+        assert!(matches!(
+            result.errors[..],
+            [Error {
+                kind: ErrorKind::MalformedCcadb { line: 1, .. },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            records("a,\"b \"\"c\"\"\",\r\n\n\"d\ne\",f\n"),
+            Ok(vec![
+                (1, vec!["a".into(), "b \"c\"".into(), "".into()]),
+                (3, vec!["d\ne".into(), "f".into()]),
+            ])
+        );
+        assert_eq!(records(""), Ok(vec![]));
+        assert_eq!(records("a\n\"b"), Err(2));
+        assert_eq!(records("a\n\"b\"c"), Err(2));
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            parse_date("2024.11.30"),
+            x509::parse_time(b"20241130000000Z")
+        );
+        assert_eq!(
+            parse_date("2024-11-30"),
+            x509::parse_time(b"20241130000000Z")
+        );
+        assert_eq!(parse_date("2024.11"), None);
+        assert_eq!(parse_date("2024.11.31"), None);
+        assert_eq!(parse_date("30/11/2024"), None);
+    }
+}
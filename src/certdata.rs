@@ -0,0 +1,290 @@
+//! Trust anchors from Mozilla's `certdata.txt`, the source of the NSS builtin
+//! root module.
+//!
+//! After a `BEGINDATA` line, the file lists PKCS#11 objects as one attribute per
+//! line, each starting with `CKA_CLASS`:
+//!
+//! ```text
+//! CKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE
+//! CKA_LABEL UTF8 "Example Root CA"
+//! CKA_VALUE MULTILINE_OCTAL
+//! \060\202\003\171\060\202\002\141\240\003\002\001\002\002\011\000
+//! ...
+//! END
+//! ```
+//!
+//! The trust settings for each certificate are in a separate `CKO_NSS_TRUST`
+//! object, which refers to the certificate by issuer and serial number.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use pki_types::CertificateDer;
+
+use crate::{x509, CertificateResult, Error, ErrorKind, ExclusionReason, Origin, Source};
+
+/// Load the trust anchors from the Mozilla `certdata.txt` file at `path`.
+///
+/// A certificate is a trust anchor if its `CKO_NSS_TRUST` object has
+/// `CKA_TRUST_SERVER_AUTH` set to `CKT_NSS_TRUSTED_DELEGATOR`. Other certificates
+/// are listed in [`CertificateResult::excluded`]:
+///
+/// - `CKT_NSS_NOT_TRUSTED` as [`ExclusionReason::Distrusted`].
+/// - `CKT_NSS_MUST_VERIFY_TRUST` as [`ExclusionReason::NotTrustedForServerAuth`].
+/// - Certificates without a trust object, or with `CKT_NSS_TRUST_UNKNOWN`, as
+///   [`ExclusionReason::NotTrusted`].
+/// - Trust anchors with a `CKA_NSS_SERVER_DISTRUST_AFTER` date as
+///   [`ExclusionReason::ServerDistrustAfter`].
+pub fn load_certs_from_certdata(path: impl AsRef<Path>) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    load_certdata(path.as_ref(), &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+pub(crate) fn load_certdata(path: &Path, out: &mut CertificateResult) {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            out.io_error(err, path, "failed to read certdata.txt");
+            return;
+        }
+    };
+
+    let objects = match objects(&String::from_utf8_lossy(&data)) {
+        Ok(objects) => objects,
+        Err(line) => {
+            out.errors.push(Error {
+                context: "failed to read certdata.txt",
+                kind: ErrorKind::MalformedCertdata {
+                    path: path.to_owned(),
+                    line,
+                },
+                from: None,
+            });
+            return;
+        }
+    };
+
+    // Server authentication trust, by issuer and serial number.
+    let trust = objects
+        .iter()
+        .filter(|object| object.class() == Some(b"CKO_NSS_TRUST"))
+        .filter_map(|object| {
+            let key = (object.get("CKA_ISSUER")?, object.get("CKA_SERIAL_NUMBER")?);
+            Some((key, object.get("CKA_TRUST_SERVER_AUTH")?))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let certs = objects
+        .iter()
+        .filter(|object| object.class() == Some(b"CKO_CERTIFICATE"));
+    for (index, object) in certs.enumerate() {
+        let Some(cert) = object.get("CKA_VALUE") else {
+            continue;
+        };
+
+        let origin = Origin::File {
+            path: path.to_owned(),
+            index,
+        };
+        let distrust_after = match object.get("CKA_NSS_SERVER_DISTRUST_AFTER") {
+            None | Some(b"CK_FALSE") => None,
+            Some(value) => match x509::parse_time(value) {
+                Some(time) => Some(time),
+                None => {
+                    out.errors.push(Error {
+                        context: "failed to read certdata.txt",
+                        kind: ErrorKind::MalformedCertdata {
+                            path: path.to_owned(),
+                            line: object.line,
+                        },
+                        from: None,
+                    });
+                    continue;
+                }
+            },
+        };
+
+        let server_auth = object
+            .get("CKA_ISSUER")
+            .zip(object.get("CKA_SERIAL_NUMBER"))
+            .and_then(|key| trust.get(&key));
+        let reason = match (server_auth, distrust_after) {
+            (Some(&b"CKT_NSS_NOT_TRUSTED"), _) => Some(ExclusionReason::Distrusted),
+            (Some(&b"CKT_NSS_MUST_VERIFY_TRUST"), _) => {
+                Some(ExclusionReason::NotTrustedForServerAuth)
+            }
+            (Some(&b"CKT_NSS_TRUSTED_DELEGATOR"), Some(time)) => {
+                Some(ExclusionReason::ServerDistrustAfter { time })
+            }
+            (Some(&b"CKT_NSS_TRUSTED_DELEGATOR"), None) => None,
+            _ => Some(ExclusionReason::NotTrusted),
+        };
+
+        let cert = CertificateDer::from(cert.to_vec());
+        match reason {
+            Some(reason) => out.exclude(cert, origin, reason),
+            None => out.push_cert(cert, origin),
+        }
+    }
+}
+
+/// An object and its attributes.
+struct Object {
+    /// The line of the object's `CKA_CLASS` attribute, starting at 1.
+    line: usize,
+    attrs: Vec<(String, Vec<u8>)>,
+}
+
+impl Object {
+    fn get(&self, name: &str) -> Option<&[u8]> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    fn class(&self) -> Option<&[u8]> {
+        self.get("CKA_CLASS")
+    }
+}
+
+/// Parse the objects in `text`, or return the number of the first malformed line.
+fn objects(text: &str) -> Result<Vec<Object>, usize> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .skip_while(|(_, line)| *line != "BEGINDATA")
+        .skip(1);
+
+    let mut objects = Vec::<Object>::new();
+    while let Some((number, line)) = lines.next() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.splitn(3, ' ');
+        let (Some(name), Some(kind)) = (parts.next(), parts.next()) else {
+            return Err(number);
+        };
+        let rest = parts.next().unwrap_or_default().trim();
+
+        let value = match kind {
+            "MULTILINE_OCTAL" => {
+                let mut value = Vec::new();
+                loop {
+                    match lines.next() {
+                        Some((_, "END")) => break,
+                        Some((number, line)) => octal(line, &mut value).ok_or(number)?,
+                        None => return Err(number),
+                    }
+                }
+                value
+            }
+            "UTF8" => rest
+                .strip_prefix('"')
+                .and_then(|rest| rest.strip_suffix('"'))
+                .ok_or(number)?
+                .as_bytes()
+                .to_vec(),
+            _ if rest.is_empty() || rest.contains(' ') => return Err(number),
+            _ => rest.as_bytes().to_vec(),
+        };
+
+        if name == "CKA_CLASS" {
+            objects.push(Object {
+                line: number,
+                attrs: Vec::new(),
+            });
+        }
+
+        match objects.last_mut() {
+            Some(object) => object
+                .attrs
+                .push((name.to_owned(), value)),
+            None => return Err(number),
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Decode a line of `\ooo` octal escapes, appending the bytes to `out`.
+fn octal(line: &str, out: &mut Vec<u8>) -> Option<()> {
+    let mut rest = line;
+    while !rest.is_empty() {
+        let digits = rest.strip_prefix('\\')?.get(..3)?;
+        out.push(u8::from_str_radix(digits, 8).ok()?);
+        rest = &rest[4..];
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pki_types::pem::PemObject;
+
+    #[test]
+    fn trust_levels() {
+        let path = Path::new("tests/certdata.txt");
+        let result = load_certs_from_certdata(path);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(
+            result.certs,
+            [
+                CertificateDer::from_pem_slice(include_bytes!("../tests/badssl-com-chain.pem"))
+                    .unwrap()
+            ]
+        );
+
+        let reasons = result
+            .excluded
+            .iter()
+            .map(|exclusion| match &exclusion.origin {
+                Origin::File { index, .. } => (*index, exclusion.reason.clone()),
+                origin => panic!("unexpected origin {origin:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            [
+                (
+                    1,
+                    ExclusionReason::ServerDistrustAfter {
+                        time: x509::parse_time(b"241130235959Z").unwrap()
+                    }
+                ),
+                (2, ExclusionReason::NotTrustedForServerAuth),
+                (3, ExclusionReason::Distrusted),
+                (4, ExclusionReason::NotTrusted),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let lines = |text: &str| objects(text).map(|objects| objects.len());
+        assert_eq!(lines("no data"), Ok(0));
+        assert_eq!(lines("BEGINDATA\nCKA_LABEL UTF8 \"x\"\n"), Err(2));
+        assert_eq!(lines("BEGINDATA\nCKA_CLASS\n"), Err(2));
+        assert_eq!(
+            lines("BEGINDATA\nCKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE\nCKA_LABEL UTF8 x\n"),
+            Err(3)
+        );
+        assert_eq!(
+            lines("BEGINDATA\nCKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE\nCKA_VALUE MULTILINE_OCTAL\n\\060\\9\n"),
+            Err(4)
+        );
+        assert_eq!(
+            lines("BEGINDATA\nCKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE\nCKA_VALUE MULTILINE_OCTAL\n\\060\n"),
+            Err(3)
+        );
+    }
+}
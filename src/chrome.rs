@@ -0,0 +1,577 @@
+//! Trust anchors from the Chrome Root Store's `root_store.textproto`.
+//!
+//! The file is a `RootStore` message in protobuf text format:
+//!
+//! ```text
+//! version_major: 37
+//!
+//! trust_anchors {
+//!   sha256_hex: "55926084ec963a64b96e2abe01ce0ba86a64fbfebcc7aab5afc155b37fd76066"
+//!   ev_policy_oids: "2.23.140.1.1"
+//!   constraints {
+//!     sct_not_after_sec: 1735689599
+//!   }
+//! }
+//! ```
+//!
+//! Each anchor gives either its certificate (`der`) or, more commonly, the
+//! SHA-256 fingerprint of a certificate in the accompanying `root_store.certs`
+//! PEM file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use pki_types::pem::PemObject;
+use pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+
+use crate::info::hex;
+use crate::{
+    CertificateResult, Error, ErrorKind, ExclusionReason, Origin, RootConstraints, RootMetadata,
+    Source,
+};
+
+/// Load the trust anchors from the Chrome Root Store at `textproto`, with their
+/// certificates from the PEM file `certs`.
+///
+/// These are the files `root_store.textproto` and `root_store.certs` in
+/// Chromium's `net/data/ssl/chrome_root_store` directory.
+///
+/// Each anchor's display name, EV policy OIDs and constraints are available from
+/// its [`Origin::RootList`]. rustls cannot enforce these constraints, so anchors
+/// whose every constraint set has an `sct_not_after_sec`, which are being phased
+/// out, are listed in [`CertificateResult::excluded`] as
+/// [`ExclusionReason::ServerDistrustAfter`].
+/// Other constraints, such as the range of Chrome versions an anchor applies to,
+/// are only recorded.
+pub fn load_certs_from_chrome_root_store(
+    textproto: impl AsRef<Path>,
+    certs: impl AsRef<Path>,
+) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    load_chrome_root_store(textproto.as_ref(), certs.as_ref(), &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+pub(crate) fn load_chrome_root_store(textproto: &Path, certs: &Path, out: &mut CertificateResult) {
+    let malformed = |out: &mut CertificateResult, line| {
+        out.errors.push(Error {
+            context: "failed to read Chrome root store",
+            kind: ErrorKind::MalformedChromeRootStore {
+                path: textproto.to_owned(),
+                line,
+            },
+            from: None,
+        });
+    };
+
+    let data = match fs::read(textproto) {
+        Ok(data) => data,
+        Err(err) => {
+            out.io_error(err, textproto, "failed to read Chrome root store");
+            return;
+        }
+    };
+    let root_store = match parse(&data) {
+        Ok(fields) => fields,
+        Err(line) => return malformed(out, line),
+    };
+
+    // The certificates are only needed for anchors that refer to them by fingerprint.
+    let mut by_sha256 = None;
+    let anchors = root_store
+        .iter()
+        .filter(|field| field.name == "trust_anchors");
+    'anchors: for (index, anchor) in anchors.enumerate() {
+        let Value::Message(fields) = &anchor.value else {
+            malformed(out, anchor.line);
+            continue;
+        };
+
+        let mut der = None;
+        let mut metadata = RootMetadata::default();
+        for field in fields {
+            match (field.name.as_str(), &field.value) {
+                ("der", Value::Scalar(value)) => der = Some(value.clone()),
+                ("sha256_hex", Value::Scalar(value)) => {
+                    metadata.sha256_fingerprint = Some(String::from_utf8_lossy(value).into_owned())
+                }
+                ("display_name", Value::Scalar(value)) => {
+                    metadata.name = Some(String::from_utf8_lossy(value).into_owned())
+                }
+                ("ev_policy_oids", Value::Scalar(value)) => metadata
+                    .ev_policy_oids
+                    .push(String::from_utf8_lossy(value).into_owned()),
+                ("constraints", Value::Message(fields)) => match constraints(fields) {
+                    Some(constraints) => metadata.constraints.push(constraints),
+                    None => {
+                        malformed(out, field.line);
+                        continue 'anchors;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let der = match (der, &metadata.sha256_fingerprint) {
+            (Some(der), _) => der,
+            (None, Some(fingerprint)) => {
+                let Some(by_sha256) = by_sha256.get_or_insert_with(|| read_certs(certs, out))
+                else {
+                    continue;
+                };
+                match by_sha256.get(&fingerprint.to_ascii_lowercase()) {
+                    Some(der) => der.clone(),
+                    None => {
+                        malformed(out, anchor.line);
+                        continue;
+                    }
+                }
+            }
+            (None, None) => {
+                malformed(out, anchor.line);
+                continue;
+            }
+        };
+
+        // An anchor is trusted if any one of its constraint sets is met, so it is
+        // only phased out if every set carries an `sct_not_after_sec`.
+        let distrust_after = metadata
+            .constraints
+            .iter()
+            .map(|constraints| constraints.sct_not_after)
+            .collect::<Option<Vec<_>>>()
+            .and_then(|times| times.into_iter().max());
+        let origin = Origin::RootList {
+            path: textproto.to_owned(),
+            index,
+            metadata: Box::new(metadata),
+        };
+        let cert = CertificateDer::from(der);
+        match distrust_after {
+            Some(time) => out.exclude(cert, origin, ExclusionReason::ServerDistrustAfter { time }),
+            None => out.push_cert(cert, origin),
+        }
+    }
+}
+
+/// Read the PEM certificates in `path`, by their lowercase hex SHA-256 fingerprint.
+///
+/// Returns `None` if the file can't be read.
+fn read_certs(path: &Path, out: &mut CertificateResult) -> Option<HashMap<String, Vec<u8>>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            out.io_error(err, path, "failed to read Chrome root store certificates");
+            return None;
+        }
+    };
+
+    let mut certs = HashMap::new();
+    for cert in CertificateDer::pem_slice_iter(&data) {
+        match cert {
+            Ok(cert) => {
+                certs.insert(hex(&Sha256::digest(&cert)), cert.to_vec());
+            }
+            Err(err) => {
+                out.pem_error(err, path);
+                break;
+            }
+        }
+    }
+
+    Some(certs)
+}
+
+fn constraints(fields: &[Field]) -> Option<RootConstraints> {
+    let mut constraints = RootConstraints::default();
+    for field in fields {
+        let Value::Scalar(value) = &field.value else {
+            return None;
+        };
+
+        let value = String::from_utf8_lossy(value).into_owned();
+        let time = || SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(value.parse().ok()?));
+        match field.name.as_str() {
+            "sct_not_after_sec" => constraints.sct_not_after = Some(time()?),
+            "sct_all_after_sec" => constraints.sct_all_after = Some(time()?),
+            "min_version" => constraints.min_version = Some(value),
+            "max_version_exclusive" => constraints.max_version_exclusive = Some(value),
+            "permitted_dns_names" => constraints
+                .permitted_dns_names
+                .push(value),
+            name => constraints
+                .other
+                .push((name.to_owned(), value)),
+        }
+    }
+    Some(constraints)
+}
+
+/// A field of a text format message.
+#[derive(Debug, PartialEq)]
+struct Field {
+    name: String,
+    value: Value,
+    /// The line the field starts on, starting at 1.
+    line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    /// A number, identifier, or (unescaped) string.
+    Scalar(Vec<u8>),
+    Message(Vec<Field>),
+}
+
+/// Parse a message in protobuf text format, or return the first malformed line.
+///
+/// Only as much of the format as the Chrome Root Store uses is supported:
+/// scalar fields, nested messages, strings with C-style escapes, and comments.
+fn parse(data: &[u8]) -> Result<Vec<Field>, usize> {
+    let mut parser = Parser {
+        data,
+        pos: 0,
+        line: 1,
+    };
+    let fields = parser.message(None)?;
+    Ok(fields)
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    /// Parse fields up to `close`, or the end of the input if `None`.
+    fn message(&mut self, close: Option<u8>) -> Result<Vec<Field>, usize> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_space();
+            let Some(&b) = self.data.get(self.pos) else {
+                return match close {
+                    None => Ok(fields),
+                    Some(_) => Err(self.line),
+                };
+            };
+
+            if Some(b) == close {
+                self.pos += 1;
+                return Ok(fields);
+            }
+
+            let line = self.line;
+            let name = self.token().ok_or(self.line)?;
+            self.skip_space();
+            let colon = self.eat(b':');
+            self.skip_space();
+
+            let value = match self.data.get(self.pos) {
+                Some(b'{') => {
+                    self.pos += 1;
+                    Value::Message(self.message(Some(b'}'))?)
+                }
+                Some(b'<') => {
+                    self.pos += 1;
+                    Value::Message(self.message(Some(b'>'))?)
+                }
+                Some(b'"' | b'\'') if colon => Value::Scalar(self.string()?),
+                _ if colon => Value::Scalar(
+                    self.token()
+                        .ok_or(self.line)?
+                        .into_bytes(),
+                ),
+                _ => return Err(self.line),
+            };
+
+            fields.push(Field { name, value, line });
+
+            self.skip_space();
+            let _ = self.eat(b',') || self.eat(b';');
+        }
+    }
+
+    /// An identifier or number.
+    fn token(&mut self) -> Option<String> {
+        let start = self.pos;
+        while let Some(b) = self.data.get(self.pos) {
+            match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'.' | b'-' | b'+' => {
+                    self.pos += 1
+                }
+                _ => break,
+            }
+        }
+
+        match start == self.pos {
+            true => None,
+            false => Some(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned()),
+        }
+    }
+
+    /// One or more adjacent quoted strings, which are concatenated.
+    fn string(&mut self) -> Result<Vec<u8>, usize> {
+        let mut out = Vec::new();
+        while let Some(&quote @ (b'"' | b'\'')) = self.data.get(self.pos) {
+            self.pos += 1;
+            loop {
+                let b = *self
+                    .data
+                    .get(self.pos)
+                    .ok_or(self.line)?;
+                self.pos += 1;
+                match b {
+                    b'\n' => return Err(self.line),
+                    b'\\' => out.push(self.escape()?),
+                    b if b == quote => break,
+                    b => out.push(b),
+                }
+            }
+            self.skip_space();
+        }
+        Ok(out)
+    }
+
+    fn escape(&mut self) -> Result<u8, usize> {
+        let b = *self
+            .data
+            .get(self.pos)
+            .ok_or(self.line)?;
+        self.pos += 1;
+        let (radix, len) = match b {
+            b'n' => return Ok(b'\n'),
+            b'r' => return Ok(b'\r'),
+            b't' => return Ok(b'\t'),
+            b'\\' | b'\'' | b'"' | b'?' => return Ok(b),
+            b'x' => (16, 2),
+            b'0'..=b'7' => {
+                self.pos -= 1;
+                (8, 3)
+            }
+            _ => return Err(self.line),
+        };
+
+        let digits = self
+            .data
+            .get(self.pos..)
+            .unwrap_or_default()
+            .iter()
+            .take(len)
+            .take_while(|&&b| (b as char).is_digit(radix))
+            .count();
+        let value = std::str::from_utf8(&self.data[self.pos..self.pos + digits])
+            .ok()
+            .and_then(|digits| u8::from_str_radix(digits, radix).ok())
+            .ok_or(self.line)?;
+        self.pos += digits;
+        Ok(value)
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        match self.data.get(self.pos) == Some(&b) {
+            true => {
+                self.pos += 1;
+                true
+            }
+            false => false,
+        }
+    }
+
+    /// Skip whitespace and `#` comments.
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.data.get(self.pos) {
+            match b {
+                b'\n' => self.line += 1,
+                b'#' => {
+                    while self
+                        .data
+                        .get(self.pos)
+                        .is_some_and(|&b| b != b'\n')
+                    {
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+                b if b.is_ascii_whitespace() => {}
+                _ => return,
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_store() {
+        let result = load_certs_from_chrome_root_store(
+            "tests/chrome-root-store/root_store.textproto",
+            "tests/chrome-root-store/root_store.certs",
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 3);
+
+        let metadata = result
+            .provenance()
+            .map(|(_, provenance)| match &provenance.origin {
+                Origin::RootList {
+                    index, metadata, ..
+                } => (*index, metadata),
+                origin => panic!("unexpected origin {origin:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(metadata[0].0, 0);
+        assert_eq!(metadata[0].1.ev_policy_oids, ["2.23.140.1.1"]);
+        assert!(metadata[0].1.constraints.is_empty());
+
+        // Given inline as `der`.
+        assert_eq!(metadata[1].0, 1);
+        assert_eq!(metadata[1].1.name.as_deref(), Some("Test Root B"));
+        assert_eq!(metadata[1].1.sha256_fingerprint, None);
+
+        assert_eq!(metadata[2].0, 3);
+        assert_eq!(
+            metadata[2].1.constraints,
+            [
+                RootConstraints {
+                    min_version: Some("120.0.6099.0".into()),
+                    ..Default::default()
+                },
+                RootConstraints {
+                    sct_all_after: Some(
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+                    ),
+                    other: vec![("max_version".into(), "130".into())],
+                    ..Default::default()
+                },
+            ]
+        );
+
+        let [exclusion] = &result.excluded[..] else {
+            panic!("unexpected exclusions {:?}", result.excluded);
+        };
+        assert_eq!(
+            exclusion.reason,
+            ExclusionReason::ServerDistrustAfter {
+                time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_735_689_599)
+            }
+        );
+        assert!(matches!(
+            exclusion.origin,
+            Origin::RootList { index: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn constraint_sets() {
+        const FINGERPRINT: &str =
+            "fc31cc459fbfa26d95f4ba432b27275d2444a88a1c13b6d3bab99a71ac18b96c";
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let textproto = temp_dir
+            .path()
+            .join("root_store.textproto");
+        let load = |constraints: &str| {
+            fs::write(
+                &textproto,
+                format!("trust_anchors {{\n  sha256_hex: \"{FINGERPRINT}\"\n{constraints}}}\n"),
+            )
+            .unwrap();
+            load_certs_from_chrome_root_store(
+                &textproto,
+                "tests/chrome-root-store/root_store.certs",
+            )
+        };
+
+        // Trusted through the set without a distrust date.
+        let result = load(
+            "constraints { sct_not_after_sec: 1735689599 }\n\
+             constraints { min_version: \"120.0.6099.0\" }\n",
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 1);
+        assert!(result.excluded.is_empty());
+
+        // Every set is phased out; the latest date is reported.
+        let result = load(
+            "constraints { sct_not_after_sec: 1735689599 }\n\
+             constraints { sct_not_after_sec: 1767225599 min_version: \"120\" }\n",
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.certs.is_empty());
+        let [exclusion] = &result.excluded[..] else {
+            panic!("unexpected exclusions {:?}", result.excluded);
+        };
+        assert_eq!(
+            exclusion.reason,
+            ExclusionReason::ServerDistrustAfter {
+                time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_767_225_599)
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_fingerprint() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let textproto = temp_dir
+            .path()
+            .join("root_store.textproto");
+        fs::write(
+            &textproto,
+            "version_major: 1\n\ntrust_anchors {\n  sha256_hex: \"00\"\n}\n",
+        )
+        .unwrap();
+
+        let result = load_certs_from_chrome_root_store(
+            &textproto,
+            "tests/chrome-root-store/root_store.certs",
+        );
+        assert!(matches!(
+            result.errors[..],
+            [Error {
+                kind: ErrorKind::MalformedChromeRootStore { line: 3, .. },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn text_format() {
+        let scalar = |name: &str, value: &[u8], line| Field {
+            name: name.into(),
+            value: Value::Scalar(value.into()),
+            line,
+        };
+
+        assert_eq!(
+            parse(b"# comment\na: 1\nb { c: \"x\\\"\\101\\x42\" 'y' }, d < >\n"),
+            Ok(vec![
+                scalar("a", b"1", 2),
+                Field {
+                    name: "b".into(),
+                    value: Value::Message(vec![scalar("c", b"x\"ABy", 3)]),
+                    line: 3,
+                },
+                Field {
+                    name: "d".into(),
+                    value: Value::Message(vec![]),
+                    line: 3,
+                },
+            ])
+        );
+        assert_eq!(parse(b""), Ok(vec![]));
+        assert_eq!(parse(b"a {\n"), Err(2));
+        assert_eq!(parse(b"a 1"), Err(1));
+        assert_eq!(parse(b"\na: \"x\n\""), Err(2));
+        assert_eq!(parse(b"a: \"\\q\""), Err(1));
+        assert_eq!(parse(b"}"), Err(1));
+    }
+}
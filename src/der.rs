@@ -0,0 +1,189 @@
+//! Just enough DER parsing to find our way around certificates and the
+//! containers they are shipped in.
+
+use crate::x509;
+
+/// Reads a sequence of DER-encoded values from a byte slice.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The data that has not been read yet.
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Read the next value, returning its tag and contents.
+    ///
+    /// Only single-byte tags and definite lengths are supported.
+    pub(crate) fn read(&mut self) -> Result<(u8, &'a [u8]), Error> {
+        let (&tag, rest) = self.data.split_first().ok_or(Error)?;
+        if tag & 0x1f == 0x1f {
+            return Err(Error);
+        }
+
+        let (&first, mut rest) = rest.split_first().ok_or(Error)?;
+        let len = match first {
+            0..=0x7f => usize::from(first),
+            0x81..=0x84 => {
+                let n = usize::from(first & 0x7f);
+                if rest.len() < n {
+                    return Err(Error);
+                }
+
+                let (len_bytes, after) = rest.split_at(n);
+                rest = after;
+                let len = len_bytes
+                    .iter()
+                    .fold(0usize, |acc, b| (acc << 8) | usize::from(*b));
+                // Reject non-minimal encodings.
+                if len < 0x80 || len_bytes[0] == 0 {
+                    return Err(Error);
+                }
+                len
+            }
+            // Indefinite (0x80) or unreasonably large lengths.
+            _ => return Err(Error),
+        };
+
+        if rest.len() < len {
+            return Err(Error);
+        }
+
+        let (contents, rest) = rest.split_at(len);
+        self.data = rest;
+        Ok((tag, contents))
+    }
+
+    /// Read the next value, which must have the given `tag`, and return its entire
+    /// encoding, including the tag and length.
+    pub(crate) fn expect_raw(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        let start = self.data;
+        self.expect(tag)?;
+        Ok(&start[..start.len() - self.data.len()])
+    }
+
+    /// Read the next value, which must have the given `tag`, and return its contents.
+    pub(crate) fn expect(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        match self.read()? {
+            (t, contents) if t == tag => Ok(contents),
+            _ => Err(Error),
+        }
+    }
+}
+
+/// Split `data` into DER-encoded certificates.
+///
+/// Returns `None` unless `data` consists entirely of one or more values that look
+/// like certificates: a SEQUENCE whose `TBSCertificate` can be read up to the
+/// subject public key. This tells certificates apart from CRLs, CSRs and private
+/// keys, which are also SEQUENCEs that start with another SEQUENCE.
+pub(crate) fn split_certificates(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut reader = Reader::new(data);
+    let mut certs = Vec::new();
+    while !reader.is_empty() {
+        let cert = reader.expect_raw(SEQUENCE).ok()?;
+        x509::subject_public_key_info(cert).ok()?;
+        certs.push(cert);
+    }
+
+    match certs.is_empty() {
+        true => None,
+        false => Some(certs),
+    }
+}
+
+/// Append the DER encoding of a value with `tag` and `contents` to `out`.
+#[cfg(any(feature = "hashed-dir", test))]
+pub(crate) fn write(tag: u8, contents: &[u8], out: &mut Vec<u8>) {
+    out.push(tag);
+    match contents.len() {
+        len @ 0..=0x7f => out.push(len as u8),
+        len => {
+            let bytes = (len as u64).to_be_bytes();
+            let skip = bytes
+                .iter()
+                .take_while(|&&b| b == 0)
+                .count();
+            out.push(0x80 | (bytes.len() - skip) as u8);
+            out.extend_from_slice(&bytes[skip..]);
+        }
+    }
+    out.extend_from_slice(contents);
+}
+
+/// Invalid or unsupported DER.
+#[derive(Debug)]
+pub(crate) struct Error;
+
+pub(crate) const SEQUENCE: u8 = 0x30;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pki_types::pem::PemObject;
+    use pki_types::CertificateDer;
+
+    #[test]
+    fn lengths() {
+        let mut reader = Reader::new(&[0x04, 0x01, 0xaa, 0x04, 0x81, 0x80]);
+        assert_eq!(reader.read().unwrap(), (0x04, &[0xaa][..]));
+        // Truncated
+        assert!(reader.read().is_err());
+
+        // Non-minimal
+        assert!(Reader::new(&[0x04, 0x81, 0x01, 0xaa])
+            .read()
+            .is_err());
+        // Indefinite
+        assert!(Reader::new(&[0x30, 0x80, 0x00, 0x00])
+            .read()
+            .is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for len in [0, 1, 0x7f, 0x80, 0xff, 0x100, 0x1_0000] {
+            let contents = vec![0xaa; len];
+            let mut encoded = Vec::new();
+            write(0x04, &contents, &mut encoded);
+            let mut reader = Reader::new(&encoded);
+            assert_eq!(reader.read().unwrap(), (0x04, &contents[..]));
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn split() {
+        assert!(split_certificates(&[]).is_none());
+        assert!(split_certificates(&[0x30, 0x00]).is_none());
+        assert!(split_certificates(&[0x30, 0x02, 0x30, 0x00, 0x0a]).is_none());
+        // Not a certificate, even though it's a SEQUENCE that starts with a SEQUENCE.
+        assert!(split_certificates(&[0x30, 0x02, 0x30, 0x00]).is_none());
+        assert!(split_certificates(include_bytes!("../tests/crl.der")).is_none());
+
+        let cert = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        let mut data = cert.to_vec();
+        data.extend_from_slice(&cert);
+        assert_eq!(
+            split_certificates(&data).unwrap(),
+            vec![&cert[..], &cert[..]]
+        );
+
+        data.extend_from_slice(include_bytes!("../tests/crl.der"));
+        assert!(split_certificates(&data).is_none());
+    }
+}
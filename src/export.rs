@@ -0,0 +1,350 @@
+//! Writing certificates for other programs: a PEM bundle for `SSL_CERT_FILE`, or
+//! a hashed directory for `SSL_CERT_DIR`.
+
+#[cfg(feature = "hashed-dir")]
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pki_types::CertificateDer;
+
+use crate::armor;
+#[cfg(feature = "hashed-dir")]
+use crate::subject_hash;
+
+/// Write `certs` to the file at `path` as a PEM bundle.
+///
+/// The bundle is written to a temporary file in the same directory, which then
+/// replaces `path`, so that readers never see a partially written bundle.
+///
+/// The result can be used as `SSL_CERT_FILE`, or loaded with
+/// [`load_certs_from_paths()`][crate::load_certs_from_paths].
+pub fn write_pem_bundle(certs: &[CertificateDer<'_>], path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp = sibling_path(path, ".tmp")?;
+    let result = (|| {
+        let mut file = create_new(&temp)?;
+        for cert in certs {
+            file.write_all(armor::encode("CERTIFICATE", cert).as_bytes())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Write `certs` to the directory at `dir`, one PEM file per certificate, in the
+/// layout created by OpenSSL's `c_rehash` or `openssl rehash`.
+///
+/// Each file is named `<hash>.<n>`, where `<hash>` is the OpenSSL subject name hash
+/// of the certificate, as 8 hexadecimal digits, and `<n>` numbers certificates
+/// with the same hash from 0. Duplicate certificates are written only once.
+///
+/// On Unix, `dir` is written as a symlink to a new directory next to it, and
+/// replaced with `rename(2)`, so that other processes find either the old or the
+/// new certificates at `dir`, never a mix or nothing. The directory the symlink
+/// pointed to before is then removed. An existing directory at `dir` that is not
+/// such a symlink is only replaced if it is empty.
+///
+/// On other platforms, the new directory is renamed into place, after moving any
+/// existing directory at `dir` aside; other processes may briefly find neither.
+///
+/// The result can be used as `SSL_CERT_DIR`, or loaded with
+/// [`load_certs_from_paths()`][crate::load_certs_from_paths].
+#[cfg(feature = "hashed-dir")]
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "hashed-dir")))]
+pub fn write_hashed_dir(certs: &[CertificateDer<'_>], dir: impl AsRef<Path>) -> io::Result<()> {
+    let dir = dir.as_ref();
+
+    // Hash everything first, so that a malformed certificate doesn't leave anything behind.
+    let mut seen = HashSet::new();
+    let mut counts = HashMap::<u32, usize>::new();
+    let mut files = Vec::with_capacity(certs.len());
+    for (index, cert) in certs.iter().enumerate() {
+        if !seen.insert(cert.as_ref()) {
+            continue;
+        }
+
+        let hash = subject_hash(cert)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("certificate {index} has a malformed subject name"),
+                )
+            })?
+            .hash;
+        let n = counts.entry(hash).or_default();
+        files.push((format!("{hash:08x}.{n}"), cert));
+        *n += 1;
+    }
+
+    let version = sibling_path(dir, "")?;
+    fs::create_dir(&version)?;
+    let result = (|| {
+        for (name, cert) in &files {
+            let mut file = create_new(&version.join(name))?;
+            file.write_all(armor::encode("CERTIFICATE", cert).as_bytes())?;
+            file.sync_all()?;
+        }
+        replace_dir(&version, dir)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&version);
+    }
+    result
+}
+
+fn create_new(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+}
+
+/// Point the symlink `to` at the directory `from`, which is next to it, and
+/// remove the directory it pointed to before.
+#[cfg(all(feature = "hashed-dir", unix))]
+fn replace_dir(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let previous = fs::read_link(to).ok();
+    if fs::symlink_metadata(to).is_ok_and(|metadata| metadata.is_dir()) {
+        // A directory can't be swapped for a symlink atomically, but an empty one
+        // has nothing to read in the meantime.
+        fs::remove_dir(to).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("'{}' is a directory that is not empty", to.display()),
+            )
+        })?;
+    }
+
+    // Relative, so that `to` and `from` can be moved together.
+    let target = from.file_name().unwrap_or_default();
+    let link = sibling_path(to, ".tmp")?;
+    std::os::unix::fs::symlink(target, &link)?;
+    if let Err(err) = fs::rename(&link, to) {
+        let _ = fs::remove_file(&link);
+        return Err(err);
+    }
+
+    // Only remove what an earlier call wrote.
+    let prefix = sibling_prefix(to)?;
+    if let Some(previous) = previous.filter(|previous| {
+        previous.parent() == Some(Path::new(""))
+            && previous
+                .as_os_str()
+                .as_bytes()
+                .starts_with(prefix.as_bytes())
+    }) {
+        let _ = fs::remove_dir_all(to.with_file_name(previous));
+    }
+    Ok(())
+}
+
+/// Move the directory `from` to `to`, replacing any directory at `to`.
+#[cfg(all(feature = "hashed-dir", not(unix)))]
+fn replace_dir(from: &Path, to: &Path) -> io::Result<()> {
+    // Replaces `to` if it is an empty directory.
+    let Err(err) = fs::rename(from, to) else {
+        return Ok(());
+    };
+    if !to.is_dir() {
+        return Err(err);
+    }
+
+    let old = sibling_path(to, ".tmp")?;
+    fs::rename(to, &old)?;
+    if let Err(err) = fs::rename(from, to) {
+        let _ = fs::rename(&old, to);
+        return Err(err);
+    }
+    fs::remove_dir_all(&old)
+}
+
+/// A hidden path next to `path`, unique to this process, ending in `suffix`.
+fn sibling_path(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let mut name = sibling_prefix(path)?;
+    name.push(format!(
+        "{}.{}{suffix}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(path.with_file_name(name))
+}
+
+/// The start of the file names given by [`sibling_path()`]: `.<name>.`.
+fn sibling_prefix(path: &Path) -> io::Result<OsString> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' does not name a file", path.display()),
+        )
+    })?;
+
+    let mut prefix = OsString::from(".");
+    prefix.push(name);
+    prefix.push(".");
+    Ok(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::load_certs_from_paths;
+    use crate::tests::certs;
+
+    #[test]
+    fn pem_bundle() {
+        let certs = [
+            certs(include_bytes!("../tests/badssl-com-chain.pem")),
+            certs(include_bytes!("../integration-tests/one-existing-ca.pem")),
+        ]
+        .concat();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("bundle.pem");
+        fs::write(&path, "old").unwrap();
+        write_pem_bundle(&certs, &path).unwrap();
+
+        let result = load_certs_from_paths(Some(&path), None);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let mut loaded = result.certs;
+        loaded.sort_by(|a, b| a.cmp(b));
+        let mut expected = certs.clone();
+        expected.sort_by(|a, b| a.cmp(b));
+        assert_eq!(loaded, expected);
+        // Only the bundle is left.
+        assert_eq!(
+            fs::read_dir(temp_dir.path())
+                .unwrap()
+                .count(),
+            1
+        );
+
+        assert!(write_pem_bundle(
+            &certs,
+            temp_dir
+                .path()
+                .join("missing/bundle.pem")
+        )
+        .is_err());
+        assert!(write_pem_bundle(&certs, "/").is_err());
+    }
+
+    #[cfg(feature = "hashed-dir")]
+    #[test]
+    fn hashed_dir() {
+        let certs = [
+            certs(include_bytes!("../tests/badssl-com-chain.pem")),
+            certs(include_bytes!("../integration-tests/one-existing-ca.pem")),
+            // Two certificates with the same subject
+            certs(include_bytes!("../tests/same-subject.pem")),
+            certs(include_bytes!("../tests/badssl-com-chain.pem")),
+        ]
+        .concat();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("certs");
+        for _ in 0..2 {
+            write_hashed_dir(&certs, &dir).unwrap();
+
+            let mut names = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| {
+                    entry
+                        .unwrap()
+                        .file_name()
+                        .into_string()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            // As named by `openssl rehash`
+            assert_eq!(
+                names,
+                ["062cdee6.0", "2e73ff31.0", "2e73ff31.1", "c275f070.0"]
+            );
+
+            let result = load_certs_from_paths(None, Some(&dir));
+            assert!(result.errors.is_empty(), "{:?}", result.errors);
+            let mut loaded = result.certs;
+            loaded.sort_by(|a, b| a.cmp(b));
+            let mut expected = certs[..4].to_vec();
+            expected.sort_by(|a, b| a.cmp(b));
+            assert_eq!(loaded, expected);
+        }
+
+        // Nothing but the directory, and on Unix the symlink to it, is left.
+        assert_eq!(
+            fs::read_dir(temp_dir.path())
+                .unwrap()
+                .count(),
+            match cfg!(unix) {
+                true => 2,
+                false => 1,
+            }
+        );
+
+        let malformed = [CertificateDer::from(vec![0x30, 0x00])];
+        assert_eq!(
+            write_hashed_dir(&malformed, &dir)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+    }
+
+    #[cfg(all(feature = "hashed-dir", unix))]
+    #[test]
+    fn hashed_dir_swap() {
+        let certs = certs(include_bytes!("../integration-tests/one-existing-ca.pem"));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("certs");
+
+        // An empty directory is replaced.
+        fs::create_dir(&dir).unwrap();
+        write_hashed_dir(&certs, &dir).unwrap();
+        let first = fs::read_link(&dir).unwrap();
+        assert_eq!(first.parent(), Some(Path::new("")));
+        assert!(dir.join("062cdee6.0").is_file());
+
+        // Each write swaps the symlink for one to a new directory.
+        write_hashed_dir(&certs, &dir).unwrap();
+        let second = fs::read_link(&dir).unwrap();
+        assert_ne!(first, second);
+        assert!(!temp_dir.path().join(first).exists());
+        assert!(dir.join("062cdee6.0").is_file());
+
+        // A directory with contents is left alone.
+        let other = temp_dir.path().join("other");
+        fs::create_dir(&other).unwrap();
+        fs::write(other.join("keep"), "").unwrap();
+        assert_eq!(
+            write_hashed_dir(&certs, &other)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert!(other.join("keep").is_file());
+        assert_eq!(
+            fs::read_dir(temp_dir.path())
+                .unwrap()
+                .count(),
+            3
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Looking up issuers in hashed certificate directories on demand, as OpenSSL's
+//! `X509_LOOKUP_hash_dir()` does.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::{env, fmt, fs, io};
+
+use pki_types::CertificateDer;
+
+use crate::subject_hash::{canonical_name, name_hash};
+use crate::{load_pem_certs, x509, CertificateResult, Exclusion, Origin, Source, ENV_CERT_DIR};
+
+/// Certificate directories, in the layout created by `openssl rehash`, that are
+/// searched only when a certificate is needed.
+///
+/// [`load_certs_from_paths()`][crate::load_certs_from_paths] reads every file in a
+/// directory. Instead, [`HashedDirs::find()`] computes the subject name hash of the
+/// requested name and reads only the files `<hash>.0`, `<hash>.1` and so on, up to
+/// the first one that does not exist. Of the certificates in those files, only those
+/// whose subject is the requested name are returned.
+///
+/// Lookups are cached per subject name, including those that find nothing. A lookup
+/// that fails with an error is not cached, and is retried the next time.
+///
+/// ```no_run
+/// use rustls_native_certs::HashedDirs;
+///
+/// let dirs = HashedDirs::new(["/etc/ssl/certs"]);
+/// let leaf = std::fs::read("leaf.der").unwrap();
+/// let result = dirs.find_issuers(&leaf.into());
+/// for issuer in result.certs {
+///     // ...
+/// }
+/// ```
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "hashed-dir")))]
+pub struct HashedDirs {
+    dirs: Vec<PathBuf>,
+    source: Source,
+    cache: Mutex<HashMap<Vec<u8>, Cached>>,
+}
+
+impl HashedDirs {
+    /// Search the directories `dirs`, in order.
+    pub fn new(dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self::with_source(
+            dirs.into_iter()
+                .map(Into::into)
+                .collect(),
+            Source::Configured,
+        )
+    }
+
+    /// Search the directories listed in `SSL_CERT_DIR`, or `None` if it is not set.
+    ///
+    /// This reads the process environment. Use
+    /// [`Loader::hashed_dirs()`][crate::Loader::hashed_dirs] to honour a loader's
+    /// configured environment instead.
+    pub fn from_env() -> Option<Self> {
+        Self::from_var(|name| env::var_os(name))
+    }
+
+    /// Search the directories listed in `SSL_CERT_DIR`, as looked up by `var`.
+    pub(crate) fn from_var(var: impl Fn(&str) -> Option<OsString>) -> Option<Self> {
+        let dirs = var(ENV_CERT_DIR)?;
+        Some(Self::with_source(
+            env::split_paths(&dirs)
+                .filter(|p| !p.as_os_str().is_empty())
+                .collect(),
+            Source::Environment,
+        ))
+    }
+
+    fn with_source(dirs: Vec<PathBuf>, source: Source) -> Self {
+        Self {
+            dirs,
+            source,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Find the certificates whose subject is `subject`, a DER-encoded name
+    /// including its outer `SEQUENCE`.
+    ///
+    /// Names are compared as OpenSSL compares them, ignoring differences in case,
+    /// whitespace and string type. The certificates are deduplicated, and carry
+    /// their [provenance](CertificateResult::provenance). An unparsable `subject`
+    /// gives an empty result.
+    pub fn find(&self, subject: &[u8]) -> CertificateResult {
+        let Ok(canonical) = canonical_name(subject) else {
+            return CertificateResult::default();
+        };
+
+        if let Some(cached) = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&canonical)
+        {
+            return cached.to_result();
+        }
+
+        // The lock isn't held while reading files, so two threads may look up the
+        // same name at once; both find the same certificates.
+        let mut found = CertificateResult::default();
+        let hash = name_hash(subject).expect("subject was parsed above");
+        for dir in &self.dirs {
+            for n in 0.. {
+                let path = dir.join(format!("{hash:08x}.{n}"));
+                match fs::metadata(&path) {
+                    Ok(metadata) if metadata.is_file() => load_pem_certs(&path, &mut found),
+                    Ok(_) => {}
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => break,
+                    Err(err) => {
+                        found.io_error(err, &path, "failed to open file");
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Different names can have the same hash.
+        let matches = |cert: &CertificateDer<'_>| {
+            x509::subject(cert)
+                .ok()
+                .and_then(|name| canonical_name(name).ok())
+                .is_some_and(|name| name == canonical)
+        };
+        found.retain(|cert| matches(cert));
+        found
+            .excluded
+            .retain(|exclusion| matches(&exclusion.cert));
+
+        if found.errors.is_empty() {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(canonical, Cached::new(&found));
+        }
+
+        let mut out = CertificateResult::default();
+        found.dedup();
+        out.extend(found, Some(self.source));
+        out
+    }
+
+    /// Find the possible issuers of `cert`: the certificates whose subject is the
+    /// issuer of `cert`.
+    ///
+    /// See [`HashedDirs::find()`]. A `cert` that can't be parsed gives an empty result.
+    pub fn find_issuers(&self, cert: &CertificateDer<'_>) -> CertificateResult {
+        match x509::issuer(cert) {
+            Ok(issuer) => self.find(issuer),
+            Err(_) => CertificateResult::default(),
+        }
+    }
+
+    /// Forget all cached lookups, so that changes to the directories are seen.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+impl fmt::Debug for HashedDirs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashedDirs")
+            .field("dirs", &self.dirs)
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The outcome of a successful lookup.
+struct Cached {
+    certs: Vec<(CertificateDer<'static>, Origin)>,
+    excluded: Vec<Exclusion>,
+}
+
+impl Cached {
+    fn new(result: &CertificateResult) -> Self {
+        Self {
+            certs: result
+                .provenance()
+                .map(|(cert, provenance)| (cert.clone(), provenance.origin.clone()))
+                .collect(),
+            excluded: result.excluded.clone(),
+        }
+    }
+
+    fn to_result(&self) -> CertificateResult {
+        let mut out = CertificateResult::default();
+        for (cert, origin) in &self.certs {
+            out.push_cert(cert.clone(), origin.clone());
+        }
+        out.excluded = self.excluded.clone();
+        out.dedup();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::certs;
+    use crate::{subject_hash, write_hashed_dir, Loader};
+
+    #[test]
+    fn find() {
+        let chain = certs(include_bytes!("../tests/badssl-com-chain.pem"));
+        let globalsign = certs(include_bytes!("../integration-tests/one-existing-ca.pem"));
+        let same_subject = certs(include_bytes!("../tests/same-subject.pem"));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("certs");
+        write_hashed_dir(&[&globalsign[..], &same_subject].concat(), &dir).unwrap();
+        // A different name with the same hash, which must not be returned.
+        fs::copy(
+            dir.join("062cdee6.0"),
+            dir.join(format!("{:08x}.0", subject_hash(&chain[0]).unwrap().hash)),
+        )
+        .unwrap();
+        // Never read.
+        fs::write(dir.join("junk.pem"), "junk").unwrap();
+
+        let dirs = HashedDirs::new([&dir, &temp_dir.path().join("missing")]);
+        let subject = x509::subject(&same_subject[0]).unwrap();
+        let result = dirs.find(subject);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let mut expected = same_subject.clone();
+        expected.sort_by(|a, b| a.cmp(b));
+        assert_eq!(result.certs, expected);
+        assert_eq!(
+            result
+                .provenance_of(&same_subject[1])
+                .unwrap()
+                .origin,
+            Origin::File {
+                path: dir.join("2e73ff31.1"),
+                index: 0
+            }
+        );
+
+        // Removing a file doesn't affect the cached lookup.
+        fs::remove_file(dir.join("2e73ff31.1")).unwrap();
+        assert_eq!(dirs.find(subject).certs, expected);
+        dirs.clear_cache();
+        assert_eq!(dirs.find(subject).certs, [same_subject[0].clone()]);
+
+        // Both certificates are self-signed.
+        assert_eq!(dirs.find_issuers(&globalsign[0]).certs, globalsign);
+        assert!(dirs
+            .find_issuers(&chain[0])
+            .certs
+            .is_empty());
+        assert!(dirs
+            .find(&[0x30, 0x01])
+            .certs
+            .is_empty());
+    }
+
+    #[test]
+    fn unreadable_file() {
+        let cert = certs(include_bytes!("../integration-tests/one-existing-ca.pem"));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("062cdee6.0")).unwrap();
+        fs::write(temp_dir.path().join("062cdee6.1"), [0x01, 0x02]).unwrap();
+
+        let dirs = HashedDirs::new([temp_dir.path()]);
+        let result = dirs.find_issuers(&cert[0]);
+        assert!(result.certs.is_empty());
+        assert_eq!(
+            result
+                .errors_from(Source::Configured)
+                .count(),
+            1
+        );
+
+        // Errors are not cached.
+        fs::remove_file(temp_dir.path().join("062cdee6.1")).unwrap();
+        assert!(dirs
+            .find_issuers(&cert[0])
+            .errors
+            .is_empty());
+    }
+
+    #[test]
+    fn loader_environment() {
+        let cert = certs(include_bytes!("../integration-tests/one-existing-ca.pem"));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("certs");
+        write_hashed_dir(&cert, &dir).unwrap();
+
+        let dirs = Loader::new()
+            .environment([(
+                ENV_CERT_DIR,
+                env::join_paths(["", dir.to_str().unwrap()]).unwrap(),
+            )])
+            .hashed_dirs()
+            .unwrap();
+        let result = dirs.find_issuers(&cert[0]);
+        assert_eq!(result.certs, cert);
+        assert_eq!(
+            result
+                .provenance_of(&cert[0])
+                .unwrap()
+                .origin,
+            Origin::File {
+                path: dir.join("062cdee6.0"),
+                index: 0
+            }
+        );
+
+        assert!(Loader::new()
+            .environment([("HOME", "/")])
+            .hashed_dirs()
+            .is_none());
+        assert!(Loader::new()
+            .environment([(ENV_CERT_DIR, &dir)])
+            .env(false)
+            .hashed_dirs()
+            .is_none());
+    }
+}
@@ -0,0 +1,456 @@
+//! Metadata parsed from certificates, so that callers don't have to parse them
+//! again.
+
+use std::fmt::Write;
+use std::time::SystemTime;
+
+use pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+
+use crate::der::{self, Reader, SEQUENCE};
+use crate::x509;
+
+/// Metadata parsed from a certificate.
+///
+/// With the `metadata` feature, this is attached to each loaded certificate as
+/// [`Provenance::info`][crate::Provenance::info].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CertificateInfo {
+    /// The subject name, formatted as described in
+    /// [RFC 4514](https://www.rfc-editor.org/rfc/rfc4514): for example,
+    /// `CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3`.
+    pub subject: String,
+    /// The issuer name, formatted like `subject`.
+    pub issuer: String,
+    /// The start of the validity period.
+    pub not_before: SystemTime,
+    /// The end of the validity period.
+    pub not_after: SystemTime,
+    /// The SHA-256 digest of the DER-encoded certificate.
+    pub sha256_fingerprint: [u8; 32],
+    /// The SHA-256 digest of the DER-encoded `SubjectPublicKeyInfo`, as used for
+    /// public key pinning.
+    pub spki_sha256: [u8; 32],
+    /// The type of the public key.
+    pub key_algorithm: KeyAlgorithm,
+    /// The size of the public key in bits, if known: the size of the modulus for
+    /// RSA, or of the curve for elliptic curve keys.
+    pub key_bits: Option<u32>,
+    /// The basic constraints extension, if present.
+    pub basic_constraints: Option<BasicConstraints>,
+}
+
+impl CertificateInfo {
+    /// Parse the metadata of `cert`, or return `None` if it is malformed.
+    pub fn parse(cert: &CertificateDer<'_>) -> Option<Self> {
+        Self::parse_der(cert).ok()
+    }
+
+    fn parse_der(cert: &[u8]) -> Result<Self, der::Error> {
+        let spki = x509::subject_public_key_info(cert)?;
+        let (not_before, not_after) = x509::validity(cert)?;
+        let (key_algorithm, key_bits) = public_key(spki)?;
+
+        let mut basic_constraints = None;
+        for extension in x509::extensions(cert)? {
+            if extension.oid == BASIC_CONSTRAINTS {
+                basic_constraints = Some(BasicConstraints::parse(extension.value)?);
+            }
+        }
+
+        Ok(Self {
+            subject: format_name(x509::subject(cert)?)?,
+            issuer: format_name(x509::issuer(cert)?)?,
+            not_before,
+            not_after,
+            sha256_fingerprint: Sha256::digest(cert).into(),
+            spki_sha256: Sha256::digest(spki).into(),
+            key_algorithm,
+            key_bits,
+            basic_constraints,
+        })
+    }
+}
+
+/// The type of a public key.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    /// RSA, including keys restricted to RSASSA-PSS.
+    Rsa,
+    /// Elliptic curve keys for ECDSA.
+    Ec,
+    /// Ed25519.
+    Ed25519,
+    /// Ed448.
+    Ed448,
+    /// Any other algorithm.
+    Other {
+        /// The algorithm's object identifier, in dotted form.
+        oid: String,
+    },
+}
+
+/// The basic constraints extension of a certificate.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BasicConstraints {
+    /// Whether the certificate is a CA certificate.
+    pub ca: bool,
+    /// The maximum number of intermediate certificates that may follow this one
+    /// in a path, if limited.
+    pub path_len: Option<u32>,
+}
+
+impl BasicConstraints {
+    pub(crate) fn parse(value: &[u8]) -> Result<Self, der::Error> {
+        let mut outer = Reader::new(value);
+        let mut constraints = Reader::new(outer.expect(SEQUENCE)?);
+        let ca = match constraints.remaining().first() == Some(&BOOLEAN) {
+            true => constraints.expect(BOOLEAN)? != [0],
+            false => false,
+        };
+
+        let path_len = match constraints.is_empty() {
+            true => None,
+            false => Some(small_integer(constraints.expect(INTEGER)?)?),
+        };
+
+        Ok(Self { ca, path_len })
+    }
+}
+
+/// The algorithm and size of the DER-encoded `SubjectPublicKeyInfo` `spki`.
+fn public_key(spki: &[u8]) -> Result<(KeyAlgorithm, Option<u32>), der::Error> {
+    let mut outer = Reader::new(spki);
+    let mut spki = Reader::new(outer.expect(SEQUENCE)?);
+    let mut algorithm = Reader::new(spki.expect(SEQUENCE)?);
+    let oid = algorithm.expect(OID)?;
+    let key = spki.expect(BIT_STRING)?;
+
+    Ok(match oid {
+        RSA_ENCRYPTION | RSASSA_PSS => {
+            let [0, key @ ..] = key else {
+                return Err(der::Error);
+            };
+            let mut outer = Reader::new(key);
+            let mut rsa = Reader::new(outer.expect(SEQUENCE)?);
+            (KeyAlgorithm::Rsa, Some(bit_length(rsa.expect(INTEGER)?)))
+        }
+        EC_PUBLIC_KEY => {
+            let bits = match algorithm.read() {
+                Ok((OID, SECP256R1)) => Some(256),
+                Ok((OID, SECP384R1)) => Some(384),
+                Ok((OID, SECP521R1)) => Some(521),
+                _ => None,
+            };
+            (KeyAlgorithm::Ec, bits)
+        }
+        ED25519 => (KeyAlgorithm::Ed25519, Some(256)),
+        ED448 => (KeyAlgorithm::Ed448, Some(448)),
+        oid => (
+            KeyAlgorithm::Other {
+                oid: dotted(oid).ok_or(der::Error)?,
+            },
+            None,
+        ),
+    })
+}
+
+/// The number of significant bits in the unsigned big-endian integer `value`.
+fn bit_length(value: &[u8]) -> u32 {
+    let start = value
+        .iter()
+        .position(|&b| b != 0)
+        .unwrap_or(value.len());
+    match value.get(start) {
+        Some(first) => (value.len() - start - 1) as u32 * 8 + (8 - first.leading_zeros()),
+        None => 0,
+    }
+}
+
+/// The contents of a non-negative `INTEGER` that fits in a `u32`.
+fn small_integer(value: &[u8]) -> Result<u32, der::Error> {
+    match value {
+        [first, ..] if first & 0x80 == 0 && value.len() <= 5 => Ok(value
+            .iter()
+            .try_fold(0u32, |acc, &b| {
+                acc.checked_mul(256)
+                    .map(|acc| acc | u32::from(b))
+            })
+            .ok_or(der::Error)?),
+        _ => Err(der::Error),
+    }
+}
+
+/// Format the DER-encoded `name` as described in RFC 4514.
+pub(crate) fn format_name(name: &[u8]) -> Result<String, der::Error> {
+    let mut rdns = Vec::new();
+    let mut reader = Reader::new(Reader::new(name).expect(SEQUENCE)?);
+    while !reader.is_empty() {
+        let mut attributes = Reader::new(reader.expect(SET)?);
+        let mut rdn = Vec::new();
+        while !attributes.is_empty() {
+            let mut attribute = Reader::new(attributes.expect(SEQUENCE)?);
+            let oid = attribute.expect(OID)?;
+            let start = attribute.remaining();
+            let (tag, contents) = attribute.read()?;
+
+            let mut out = match ATTRIBUTE_TYPES
+                .iter()
+                .find(|(known, _)| *known == oid)
+            {
+                Some((_, name)) => name.to_string(),
+                None => dotted(oid).ok_or(der::Error)?,
+            };
+            out.push('=');
+            match decode_string(tag, contents) {
+                Some(value) => escape(&value, &mut out),
+                // Values that are not strings are written as the hex of their encoding.
+                None => {
+                    let value = &start[..start.len() - attribute.remaining().len()];
+                    out.push('#');
+                    out.push_str(&hex(value));
+                }
+            }
+            rdn.push(out);
+        }
+        rdns.push(rdn.join("+"));
+    }
+
+    // RFC 4514 starts with the last RDN.
+    rdns.reverse();
+    Ok(rdns.join(","))
+}
+
+/// Append `value` to `out`, escaped as an RFC 4514 attribute value.
+fn escape(value: &str, out: &mut String) {
+    let last = value.chars().count().saturating_sub(1);
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '#' if i == 0 => out.push_str("\\#"),
+            ' ' if i == 0 || i == last => out.push_str("\\ "),
+            '\0' => out.push_str("\\00"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// The dotted form of the object identifier with contents `oid`, such as
+/// `2.5.4.3`.
+pub(crate) fn dotted(oid: &[u8]) -> Option<String> {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for (i, &b) in oid.iter().enumerate() {
+        arc = arc.checked_mul(128)? | u64::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        } else if i == oid.len() - 1 {
+            return None;
+        }
+    }
+
+    let first = *arcs.first()?;
+    let mut out = match first {
+        0..=39 => format!("0.{first}"),
+        40..=79 => format!("1.{}", first - 40),
+        _ => format!("2.{}", first - 80),
+    };
+    for arc in &arcs[1..] {
+        write!(out, ".{arc}").unwrap();
+    }
+    Some(out)
+}
+
+/// Decode a string value with `tag` and `contents` from a name.
+///
+/// Returns `None` for values that are not strings, or can't be decoded.
+pub(crate) fn decode_string(tag: u8, contents: &[u8]) -> Option<String> {
+    Some(match tag {
+        UTF8_STRING | PRINTABLE_STRING | IA5_STRING | VISIBLE_STRING => {
+            String::from_utf8_lossy(contents).into_owned()
+        }
+        // Treated as Latin-1, as OpenSSL does.
+        T61_STRING => contents
+            .iter()
+            .map(|&b| char::from(b))
+            .collect(),
+        BMP_STRING if contents.len() % 2 == 0 => char::decode_utf16(
+            contents
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]])),
+        )
+        .collect::<Result<String, _>>()
+        .ok()?,
+        UNIVERSAL_STRING => contents
+            .chunks(4)
+            .map(|quad| char::from_u32(u32::from_be_bytes(quad.try_into().ok()?)))
+            .collect::<Option<String>>()?,
+        _ => return None,
+    })
+}
+
+/// Format `bytes` as lowercase hexadecimal.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Short names of attribute types, from RFC 4514 and RFC 4519.
+const ATTRIBUTE_TYPES: &[(&[u8], &str)] = &[
+    (&[0x55, 0x04, 0x03], "CN"),
+    (&[0x55, 0x04, 0x05], "serialNumber"),
+    (&[0x55, 0x04, 0x06], "C"),
+    (&[0x55, 0x04, 0x07], "L"),
+    (&[0x55, 0x04, 0x08], "ST"),
+    (&[0x55, 0x04, 0x09], "STREET"),
+    (&[0x55, 0x04, 0x0a], "O"),
+    (&[0x55, 0x04, 0x0b], "OU"),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01],
+        "UID",
+    ),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19],
+        "DC",
+    ),
+];
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OID: u8 = 0x06;
+pub(crate) const UTF8_STRING: u8 = 0x0c;
+pub(crate) const PRINTABLE_STRING: u8 = 0x13;
+pub(crate) const T61_STRING: u8 = 0x14;
+pub(crate) const IA5_STRING: u8 = 0x16;
+pub(crate) const VISIBLE_STRING: u8 = 0x1a;
+pub(crate) const UNIVERSAL_STRING: u8 = 0x1c;
+pub(crate) const BMP_STRING: u8 = 0x1e;
+const SET: u8 = 0x31;
+
+/// 2.5.29.19
+const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+/// 1.2.840.113549.1.1.1
+const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+/// 1.2.840.113549.1.1.10
+const RSASSA_PSS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0a];
+/// 1.2.840.10045.2.1
+const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+/// 1.2.840.10045.3.1.7
+const SECP256R1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+/// 1.3.132.0.34
+const SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+/// 1.3.132.0.35
+const SECP521R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23];
+/// 1.3.101.112
+const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+/// 1.3.101.113
+const ED448: &[u8] = &[0x2b, 0x65, 0x71];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pki_types::pem::PemObject;
+
+    #[test]
+    fn globalsign() {
+        let cert = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        let info = CertificateInfo::parse(&cert).unwrap();
+
+        // As printed by `openssl x509 -nameopt RFC2253 -subject -issuer`
+        assert_eq!(
+            info.subject,
+            "CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3"
+        );
+        assert_eq!(info.issuer, info.subject);
+        assert_eq!(
+            info.not_after
+                .duration_since(info.not_before)
+                .unwrap()
+                .as_secs(),
+            1_868_522_400 - 1_237_370_400
+        );
+        // As printed by `openssl x509 -fingerprint -sha256`
+        assert_eq!(
+            hex(&info.sha256_fingerprint),
+            "cbb522d7b7f127ad6a0113865bdf1cd4102e7d0759af635a7cf4720dc963c53b"
+        );
+        assert_eq!(info.key_algorithm, KeyAlgorithm::Rsa);
+        assert_eq!(info.key_bits, Some(2048));
+        assert_eq!(
+            info.basic_constraints,
+            Some(BasicConstraints {
+                ca: true,
+                path_len: None
+            })
+        );
+    }
+
+    #[test]
+    fn names() {
+        let name = |attributes: &[(&[u8], u8, &[u8])]| {
+            let mut contents = Vec::new();
+            for (oid, tag, value) in attributes {
+                let mut attribute = Vec::new();
+                der::write(OID, oid, &mut attribute);
+                der::write(*tag, value, &mut attribute);
+                let mut sequence = Vec::new();
+                der::write(SEQUENCE, &attribute, &mut sequence);
+                der::write(SET, &sequence, &mut contents);
+            }
+            let mut out = Vec::new();
+            der::write(SEQUENCE, &contents, &mut out);
+            format_name(&out).unwrap()
+        };
+
+        assert_eq!(
+            name(&[
+                (&[0x55, 0x04, 0x06], 0x13, b"US"),
+                (&[0x55, 0x04, 0x03], 0x0c, b" Example, Inc. "),
+            ]),
+            "CN=\\ Example\\, Inc.\\ ,C=US"
+        );
+        assert_eq!(name(&[(&[0x2a, 0x03], 0x04, b"\x01")]), "1.2.3=#040101");
+        assert_eq!(name(&[]), "");
+    }
+
+    #[test]
+    fn oids() {
+        assert_eq!(dotted(&[0x55, 0x04, 0x03]).as_deref(), Some("2.5.4.3"));
+        assert_eq!(
+            dotted(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01]).as_deref(),
+            Some("1.2.840.113549.1.1.1")
+        );
+        assert_eq!(dotted(&[0x88, 0x37, 0x03]).as_deref(), Some("2.999.3"));
+        assert_eq!(dotted(&[0x2a, 0x86]), None);
+        assert_eq!(dotted(&[]), None);
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(bit_length(&[0x00, 0x80, 0x00]), 16);
+        assert_eq!(bit_length(&[0x01]), 1);
+        assert_eq!(bit_length(&[0x00]), 0);
+        assert_eq!(small_integer(&[0x00]).ok(), Some(0));
+        assert_eq!(
+            small_integer(&[0x00, 0xff, 0xff, 0xff, 0xff]).ok(),
+            Some(u32::MAX)
+        );
+        assert!(small_integer(&[0x01, 0x00, 0x00, 0x00, 0x00]).is_err());
+        assert!(small_integer(&[0x80]).is_err());
+        assert!(small_integer(&[]).is_err());
+    }
+}
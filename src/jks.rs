@@ -0,0 +1,305 @@
+//! Trusted certificate entries from Java keystores, in the JKS and JCEKS formats
+//! written by `keytool`, such as `$JAVA_HOME/lib/security/cacerts`.
+//!
+//! Both formats share the same layout: a header, a list of entries, and a SHA-1
+//! digest over the password and everything before it. Private key entries are
+//! skipped. JCEKS secret key entries are Java-serialized objects, which are not
+//! supported.
+
+use std::path::Path;
+use std::{fmt, fs};
+
+use pki_types::CertificateDer;
+use sha1::{Digest, Sha1};
+
+use crate::{CertificateResult, Error, ErrorKind, Origin, Source};
+
+/// Load the trusted certificate entries from the Java keystore at `path`.
+///
+/// Both JKS and JCEKS keystores are supported. If `password` is given, it is used
+/// to verify the keystore's integrity; otherwise, the check is skipped. Trusted
+/// certificate entries are not encrypted, so the password is not needed to read
+/// them.
+///
+/// The alias of each certificate is available as an [`Origin::Keystore`] from
+/// [`CertificateResult::provenance()`]. Keystores that cannot be read are reported
+/// as an [`ErrorKind::Keystore`] error.
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "jks")))]
+pub fn load_certs_from_java_keystore(
+    path: impl AsRef<Path>,
+    password: Option<&str>,
+) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    load_keystore(path.as_ref(), password, &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+pub(crate) fn load_keystore(path: &Path, password: Option<&str>, out: &mut CertificateResult) {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            out.io_error(err, path, "failed to read Java keystore");
+            return;
+        }
+    };
+
+    match entries(&data, password) {
+        Ok(entries) => {
+            for (alias, cert) in entries {
+                let origin = Origin::Keystore {
+                    path: path.to_owned(),
+                    alias,
+                };
+                out.push_cert(CertificateDer::from(cert.to_vec()), origin);
+            }
+        }
+        Err(inner) => out.errors.push(Error {
+            context: "failed to read Java keystore",
+            kind: ErrorKind::Keystore {
+                inner,
+                path: path.to_owned(),
+            },
+            from: None,
+        }),
+    }
+}
+
+/// Parse a keystore, returning the alias and DER encoding of each trusted certificate.
+fn entries<'a>(
+    data: &'a [u8],
+    password: Option<&str>,
+) -> Result<Vec<(String, &'a [u8])>, KeystoreError> {
+    let mut reader = Reader(data);
+    let magic = reader.u32()?;
+    if magic != JKS_MAGIC && magic != JCEKS_MAGIC {
+        return Err(KeystoreError::Malformed);
+    }
+
+    let version = reader.u32()?;
+    if version != 1 && version != 2 {
+        return Err(KeystoreError::UnsupportedVersion(version));
+    }
+
+    let mut entries = Vec::new();
+    for _ in 0..reader.u32()? {
+        let tag = reader.u32()?;
+        let alias = reader.utf()?;
+        reader.bytes(8)?; // creation date
+        match tag {
+            PRIVATE_KEY => {
+                reader.sized()?; // encrypted key
+                for _ in 0..reader.u32()? {
+                    reader.certificate(version)?;
+                }
+            }
+            TRUSTED_CERT => entries.push((alias, reader.certificate(version)?)),
+            _ => return Err(KeystoreError::UnsupportedEntry(tag)),
+        }
+    }
+
+    let digest = reader.bytes(DIGEST_LEN)?;
+    if !reader.0.is_empty() {
+        return Err(KeystoreError::Malformed);
+    }
+
+    if let Some(password) = password {
+        let mut hasher = Sha1::new();
+        for unit in password.encode_utf16() {
+            hasher.update(unit.to_be_bytes());
+        }
+        hasher.update(WHITENER);
+        hasher.update(&data[..data.len() - DIGEST_LEN]);
+        if hasher.finalize().as_slice() != digest {
+            return Err(KeystoreError::IntegrityMismatch);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Reads the big-endian values written by Java's `DataOutputStream`.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], KeystoreError> {
+        if self.0.len() < len {
+            return Err(KeystoreError::Malformed);
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, KeystoreError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a byte array prefixed by its 32-bit length.
+    fn sized(&mut self) -> Result<&'a [u8], KeystoreError> {
+        let len = self.u32()?;
+        self.bytes(usize::try_from(len).map_err(|_| KeystoreError::Malformed)?)
+    }
+
+    fn u16(&mut self) -> Result<u16, KeystoreError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Read a string in Java's "modified UTF-8".
+    fn utf(&mut self) -> Result<String, KeystoreError> {
+        let len = self.u16()?;
+        let mut bytes = self.bytes(usize::from(len))?.iter();
+        let mut units = Vec::with_capacity(bytes.len());
+        while let Some(&first) = bytes.next() {
+            let mut next = || match bytes.next() {
+                Some(&b) if b & 0xc0 == 0x80 => Ok(u16::from(b & 0x3f)),
+                _ => Err(KeystoreError::Malformed),
+            };
+
+            units.push(match first {
+                0x01..=0x7f => u16::from(first),
+                0xc0..=0xdf => (u16::from(first & 0x1f) << 6) | next()?,
+                0xe0..=0xef => (u16::from(first & 0x0f) << 12) | (next()? << 6) | next()?,
+                _ => return Err(KeystoreError::Malformed),
+            });
+        }
+
+        String::from_utf16(&units).map_err(|_| KeystoreError::Malformed)
+    }
+
+    /// Read a certificate, returning its encoding.
+    fn certificate(&mut self, version: u32) -> Result<&'a [u8], KeystoreError> {
+        if version == 2 && self.utf()? != "X.509" {
+            return Err(KeystoreError::Malformed);
+        }
+
+        self.sized()
+    }
+}
+
+/// Why a Java keystore could not be read.
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "jks")))]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeystoreError {
+    /// The file is not a valid JKS or JCEKS keystore.
+    Malformed,
+    /// The keystore's format version is not supported.
+    UnsupportedVersion(u32),
+    /// The keystore contains an entry of a type that cannot be read, such as a
+    /// JCEKS secret key entry. Contains the entry's tag.
+    UnsupportedEntry(u32),
+    /// The integrity check failed; the keystore is corrupt or the password is wrong.
+    IntegrityMismatch,
+}
+
+impl fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => f.write_str("malformed Java keystore"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported Java keystore version {version}")
+            }
+            Self::UnsupportedEntry(tag) => write!(f, "unsupported Java keystore entry type {tag}"),
+            Self::IntegrityMismatch => {
+                f.write_str("keystore integrity check failed (wrong password?)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeystoreError {}
+
+const JKS_MAGIC: u32 = 0xfeed_feed;
+const JCEKS_MAGIC: u32 = 0xcece_cece;
+
+const PRIVATE_KEY: u32 = 1;
+const TRUSTED_CERT: u32 = 2;
+
+const DIGEST_LEN: usize = 20;
+/// Mixed into the integrity digest by `sun.security.provider.JavaKeyStore`.
+const WHITENER: &[u8] = b"Mighty Aphrodite";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keystores() {
+        for path in ["tests/keystore.jks", "tests/keystore.jceks"] {
+            for password in [Some("changeit"), None] {
+                let result = load_certs_from_java_keystore(path, password);
+                assert!(result.errors.is_empty(), "{path}: {:?}", result.errors);
+                assert_eq!(result.certs.len(), 2, "{path}");
+
+                let mut aliases = result
+                    .provenance()
+                    .map(|(_, provenance)| match &provenance.origin {
+                        Origin::Keystore { alias, .. } => alias.as_str(),
+                        origin => panic!("unexpected origin {origin:?}"),
+                    })
+                    .collect::<Vec<_>>();
+                aliases.sort();
+                assert_eq!(aliases, ["badssl", "globalsign root ca - r3"]);
+            }
+        }
+    }
+
+    #[test]
+    fn private_key_entries_are_skipped() {
+        let result = load_certs_from_java_keystore("tests/keystore-mixed.jks", Some("changeit"));
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 1);
+    }
+
+    #[test]
+    fn wrong_password() {
+        let result = load_certs_from_java_keystore("tests/keystore.jks", Some("wrong"));
+        assert!(result.certs.is_empty());
+        match &result.errors[0].kind {
+            ErrorKind::Keystore { inner, .. } => {
+                assert_eq!(*inner, KeystoreError::IntegrityMismatch)
+            }
+            _ => panic!("unexpected error {:?}", result.errors),
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let data = include_bytes!("../tests/keystore.jks");
+        assert_eq!(
+            entries(&data[..data.len() - 1], None),
+            Err(KeystoreError::Malformed)
+        );
+        assert_eq!(
+            entries(include_bytes!("../tests/truststore.p12"), None),
+            Err(KeystoreError::Malformed)
+        );
+
+        let mut data = data.to_vec();
+        data[7] = 3;
+        assert_eq!(
+            entries(&data, None),
+            Err(KeystoreError::UnsupportedVersion(3))
+        );
+    }
+
+    #[test]
+    fn modified_utf8() {
+        // "a\0é€😀", with NUL and the emoji encoded as Java does.
+        let data = [
+            0x00, 0x0e, 0x61, 0xc0, 0x80, 0xc3, 0xa9, 0xe2, 0x82, 0xac, 0xed, 0xa0, 0xbd, 0xed,
+            0xb8, 0x80,
+        ];
+        assert_eq!(Reader(&data).utf().unwrap(), "a\0é€😀");
+        assert_eq!(
+            Reader(&[0x00, 0x01, 0x80]).utf(),
+            Err(KeystoreError::Malformed)
+        );
+    }
+}
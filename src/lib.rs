@@ -8,6 +8,9 @@
 //! If the SSL_CERT_FILE environment variable is set, certificates (in PEM
 //! format) are read from that file instead.
 //!
+//! If you need more control over where certificates are loaded from, use
+//! a [`Loader`].
+//!
 //! If you want to load these certificates into a `rustls::RootCertStore`,
 //! you'll likely want to do something like this:
 //!
@@ -21,13 +24,80 @@
 // Enable documentation for all features on docs.rs
 #![cfg_attr(rustls_native_certs_docsrs, feature(doc_cfg))]
 
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
 use std::error::Error as StdError;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
-use std::{env, fmt, fs, io};
+use std::time::SystemTime;
+use std::{env, fmt, fs, io, mem};
 
-use pki_types::pem::{self, PemObject};
+use pki_types::pem::{self, SectionKind};
 use pki_types::CertificateDer;
 
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "archive")]
+pub use archive::{load_certs_from_archive, ArchiveError, MAX_DECOMPRESSED_SIZE};
+
+mod armor;
+mod blocklist;
+pub use blocklist::{BlockRule, Blocklist};
+mod der;
+mod export;
+#[cfg(feature = "hashed-dir")]
+pub use export::write_hashed_dir;
+pub use export::write_pem_bundle;
+#[cfg(feature = "hashed-dir")]
+mod hashed_dir;
+#[cfg(feature = "hashed-dir")]
+pub use hashed_dir::HashedDirs;
+mod info;
+pub use info::{BasicConstraints, CertificateInfo, KeyAlgorithm};
+mod pkcs7;
+mod policy;
+pub use policy::{CaPolicy, KeyPurpose};
+mod scan;
+pub use scan::{
+    load_certs_from_dir, DirOptions, SkipReason, Skipped, SymlinkContainment, SymlinkPolicy,
+};
+#[cfg(feature = "hashed-dir")]
+mod subject_hash;
+#[cfg(feature = "hashed-dir")]
+pub use subject_hash::{subject_hash, SubjectHash};
+mod trusted;
+mod x509;
+
+mod ccadb;
+pub use ccadb::{load_certs_from_ccadb, RootProgram};
+
+mod certdata;
+pub use certdata::load_certs_from_certdata;
+
+mod chrome;
+pub use chrome::load_certs_from_chrome_root_store;
+
+mod p11kit;
+pub use p11kit::load_certs_from_p11kit;
+
+#[cfg(feature = "jks")]
+mod jks;
+#[cfg(feature = "jks")]
+pub use jks::{load_certs_from_java_keystore, KeystoreError};
+
+#[cfg(feature = "nssdb")]
+mod nssdb;
+#[cfg(feature = "nssdb")]
+pub use nssdb::{load_certs_from_nss_db, user_nss_db};
+
+#[cfg(feature = "pkcs12")]
+mod pkcs12;
+#[cfg(feature = "pkcs12")]
+pub use pkcs12::{load_certs_from_pkcs12, Pkcs12Error};
+
+mod loader;
+pub use loader::Loader;
+
 #[cfg(all(unix, not(target_os = "macos")))]
 mod unix;
 #[cfg(all(unix, not(target_os = "macos")))]
@@ -51,15 +121,25 @@ use macos as platform;
 /// |---------------|---------------------------------------------------------------------------------------|
 /// | SSL_CERT_FILE | File containing an arbitrary number of certificates in PEM format.                    |
 /// | SSL_CERT_DIR  | Colon separated list of directories containing certificate files.                     |
+/// | RUSTLS_NATIVE_CERTS_ADDITIVE | If set to a value other than `0` or `false`, also load the platform-native store. |
+/// | RUSTLS_NATIVE_CERTS_BLOCKLIST | A [blocklist](Blocklist) file of roots to exclude, wherever they were loaded from. |
 ///
-/// If **either** (or **both**) are set, certificates are only loaded from
-/// the locations specified via environment variables and not the platform-
-/// native certificate store.
+/// If **either** (or **both**) of `SSL_CERT_FILE` and `SSL_CERT_DIR` are set,
+/// certificates are only loaded from the locations specified via environment
+/// variables and not the platform-native certificate store, unless
+/// `RUSTLS_NATIVE_CERTS_ADDITIVE` is set. In that case, certificates from both
+/// are merged and deduplicated.
 ///
 /// ## Certificate Validity
 ///
-/// All certificates are expected to be in PEM format. A file may contain
-/// multiple certificates.
+/// Certificates are expected to be in PEM format. A file may contain
+/// multiple certificates. Files containing one or more DER-encoded certificates
+/// (as is common for `.der` and `.cer` files) are also accepted, as are PKCS#7
+/// certificate bundles (`.p7b` and `.p7c` files) in PEM or DER form.
+///
+/// OpenSSL `TRUSTED CERTIFICATE` blocks are loaded as well. Those whose trust
+/// settings do not allow server authentication are listed in
+/// [`CertificateResult::excluded`] instead of [`CertificateResult::certs`].
 ///
 /// Example:
 ///
@@ -116,12 +196,27 @@ use macos as platform;
 ///
 /// [c_rehash]: https://www.openssl.org/docs/manmaster/man1/c_rehash.html
 pub fn load_native_certs() -> CertificateResult {
-    let paths = CertPaths::from_env();
-    match (&paths.dirs, &paths.file) {
-        (v, _) if !v.is_empty() => paths.load(),
-        (_, Some(_)) => paths.load(),
-        _ => platform::load_native_certs(),
+    let var = |name: &str| env::var_os(name);
+    let paths = CertPaths::from_env(var);
+    let additive = additive_from_env(var);
+
+    let mut out = CertificateResult::default();
+    if !paths.is_empty() {
+        out.extend(paths.load(), Some(Source::Environment));
     }
+
+    if paths.is_empty() || additive {
+        out.extend(platform::load_native_certs(), Some(Source::Platform));
+    }
+
+    // The platform store is returned as is; only merging it with the certificates
+    // from the environment calls for deduplication.
+    if !paths.is_empty() && additive {
+        out.dedup();
+    }
+
+    blocklist::apply_env(blocklist_from_env(var).as_deref(), &mut out);
+    out
 }
 
 /// Results from trying to load certificates from the platform's native store.
@@ -132,6 +227,18 @@ pub struct CertificateResult {
     pub certs: Vec<CertificateDer<'static>>,
     /// Any errors encountered while loading certificates.
     pub errors: Vec<Error>,
+    /// Problems with certificates that were loaded anyway.
+    ///
+    /// Unlike [`CertificateResult::errors`], these don't make
+    /// [`CertificateResult::expect()`] or [`CertificateResult::unwrap()`] panic.
+    pub warnings: Vec<Error>,
+    /// Certificates that were found but left out of `certs`, and why.
+    pub excluded: Vec<Exclusion>,
+    /// Files and directories that were passed over while scanning directories, and why.
+    pub skipped: Vec<Skipped>,
+    /// Where each certificate loaded into this result was found, keyed by its
+    /// contents so that it survives changes to `certs`.
+    provenance: HashMap<CertificateDer<'static>, Provenance>,
 }
 
 impl CertificateResult {
@@ -156,6 +263,201 @@ impl CertificateResult {
         }
     }
 
+    /// Add the certificates, errors, warnings, exclusions and skipped files from
+    /// `other` to this result.
+    ///
+    /// The combined certificates are sorted and deduplicated. The sources of
+    /// errors from both results are preserved.
+    pub fn merge(&mut self, other: Self) {
+        self.extend(other, None);
+        self.dedup();
+    }
+
+    /// Iterate over the errors that came from `source`.
+    ///
+    /// This is shorthand for filtering [`CertificateResult::errors`] on
+    /// [`Error::from`].
+    pub fn errors_from(&self, source: Source) -> impl Iterator<Item = &Error> {
+        self.errors
+            .iter()
+            .filter(move |err| err.from == Some(source))
+    }
+
+    /// Iterate over the certificates along with where they were loaded from.
+    ///
+    /// Certificates pushed onto [`CertificateResult::certs`] by the caller have
+    /// no known provenance, and are skipped.
+    pub fn provenance(&self) -> impl Iterator<Item = (&CertificateDer<'static>, &Provenance)> {
+        self.certs
+            .iter()
+            .filter_map(|cert| Some((cert, self.provenance.get(cert)?)))
+    }
+
+    /// Return where `cert` was loaded from, if it is part of this result.
+    pub fn provenance_of(&self, cert: &CertificateDer<'_>) -> Option<&Provenance> {
+        let cert = self.certs.iter().find(|c| *c == cert)?;
+        self.provenance.get(cert)
+    }
+
+    fn push_cert(&mut self, cert: CertificateDer<'static>, origin: Origin) {
+        #[cfg(feature = "metadata")]
+        let info = match CertificateInfo::parse(&cert) {
+            Some(info) => Some(Box::new(info)),
+            None => {
+                self.warnings.push(Error {
+                    context: "failed to parse certificate",
+                    kind: ErrorKind::MalformedCertificate {
+                        origin: origin.clone(),
+                    },
+                    from: None,
+                });
+                None
+            }
+        };
+
+        self.record(
+            cert.clone(),
+            Provenance {
+                origin,
+                source: None,
+                shadowed: Vec::new(),
+                #[cfg(feature = "metadata")]
+                info,
+            },
+        );
+        self.certs.push(cert);
+    }
+
+    /// Record `provenance` for `cert`, or add its origins to the shadowed ones if
+    /// the certificate was already loaded.
+    fn record(&mut self, cert: CertificateDer<'static>, provenance: Provenance) {
+        match self.provenance.entry(cert) {
+            Entry::Occupied(mut entry) => {
+                let first = entry.get_mut();
+                first.shadowed.push(provenance.origin);
+                first
+                    .shadowed
+                    .extend(provenance.shadowed);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(provenance);
+            }
+        }
+    }
+
+    fn exclude(&mut self, cert: CertificateDer<'static>, origin: Origin, reason: ExclusionReason) {
+        self.excluded.push(Exclusion {
+            cert,
+            origin,
+            reason,
+        });
+    }
+
+    /// Append `other` to this result, recording `source` for errors, warnings and
+    /// certificates in `other` that don't have one yet.
+    fn extend(&mut self, mut other: Self, source: Option<Source>) {
+        for err in other
+            .errors
+            .iter_mut()
+            .chain(&mut other.warnings)
+        {
+            err.from = err.from.or(source);
+        }
+
+        // Record provenance in the order the certificates were loaded, so that
+        // the first copy of a certificate stays its origin.
+        for cert in &other.certs {
+            if let Some(mut provenance) = other.provenance.remove(cert) {
+                provenance.source = provenance.source.or(source);
+                self.record(cert.clone(), provenance);
+            }
+        }
+
+        self.certs.extend(other.certs);
+        self.errors.extend(other.errors);
+        self.warnings.extend(other.warnings);
+        self.excluded.extend(other.excluded);
+        self.skipped.extend(other.skipped);
+    }
+
+    /// Keep only the certificates for which `f` returns `true`.
+    fn retain(&mut self, mut f: impl FnMut(&CertificateDer<'static>) -> bool) {
+        self.certs.retain(|cert| f(cert));
+        let kept = self
+            .certs
+            .iter()
+            .collect::<HashSet<_>>();
+        self.provenance
+            .retain(|cert, _| kept.contains(cert));
+    }
+
+    /// Move the certificates for which `f` returns a reason to `excluded`.
+    ///
+    /// Certificates without a known provenance are excluded with
+    /// [`Origin::Unknown`].
+    fn exclude_where(
+        &mut self,
+        mut f: impl FnMut(&CertificateDer<'static>) -> Option<ExclusionReason>,
+    ) {
+        // Each copy of an excluded certificate is reported with its own origin,
+        // in the order the copies were loaded.
+        let mut origins = HashMap::<_, (Vec<Origin>, usize)>::new();
+        for cert in mem::take(&mut self.certs) {
+            let Some(reason) = f(&cert) else {
+                self.certs.push(cert);
+                continue;
+            };
+
+            let (copies, seen) = origins
+                .entry(cert.clone())
+                .or_insert_with(|| {
+                    let mut copies = Vec::new();
+                    if let Some(provenance) = self.provenance.get(&cert) {
+                        copies.push(provenance.origin.clone());
+                        copies.extend_from_slice(&provenance.shadowed);
+                    }
+                    (copies, 0)
+                });
+            let origin = copies
+                .get(*seen)
+                .or(copies.last())
+                .cloned()
+                .unwrap_or(Origin::Unknown);
+            *seen += 1;
+            self.exclude(cert, origin, reason);
+        }
+
+        for cert in origins.keys() {
+            self.provenance.remove(cert);
+        }
+    }
+
+    /// Exclude certificates that are not valid at `now`.
+    ///
+    /// Certificates whose validity period can't be parsed are kept.
+    fn exclude_invalid_at(&mut self, now: SystemTime) {
+        self.exclude_where(|cert| {
+            let (not_before, not_after) = x509::validity(cert).ok()?;
+            if now < not_before {
+                Some(ExclusionReason::NotYetValid { not_before })
+            } else if now > not_after {
+                Some(ExclusionReason::Expired { not_after })
+            } else {
+                None
+            }
+        });
+    }
+
+    /// Sort and deduplicate the certificates.
+    ///
+    /// The origins of duplicates are already recorded as shadowed, as each one
+    /// is loaded.
+    fn dedup(&mut self) {
+        self.certs
+            .sort_unstable_by(|a, b| a.cmp(b));
+        self.certs.dedup();
+    }
+
     fn pem_error(&mut self, err: pem::Error, path: &Path) {
         self.errors.push(Error {
             context: "failed to read PEM from file",
@@ -166,6 +468,27 @@ impl CertificateResult {
                 },
                 _ => ErrorKind::Pem(err),
             },
+            from: None,
+        });
+    }
+
+    fn pkcs7_error(&mut self, path: &Path) {
+        self.errors.push(Error {
+            context: "failed to read PKCS#7 bundle from file",
+            kind: ErrorKind::MalformedPkcs7 {
+                path: path.to_owned(),
+            },
+            from: None,
+        });
+    }
+
+    fn trusted_certificate_error(&mut self, path: &Path) {
+        self.errors.push(Error {
+            context: "failed to read trusted certificate from file",
+            kind: ErrorKind::MalformedTrustedCertificate {
+                path: path.to_owned(),
+            },
+            from: None,
         });
     }
 
@@ -176,6 +499,7 @@ impl CertificateResult {
                 inner: err,
                 path: path.to_owned(),
             },
+            from: None,
         });
     }
 
@@ -184,25 +508,250 @@ impl CertificateResult {
         self.errors.push(Error {
             context,
             kind: ErrorKind::Os(err),
+            from: None,
         });
     }
 }
 
+/// Where certificates or errors in a [`CertificateResult`] came from.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// Files and directories set via `SSL_CERT_FILE` and `SSL_CERT_DIR`.
+    Environment,
+    /// The platform's native certificate store.
+    Platform,
+    /// Files and directories configured explicitly, for example with
+    /// [`Loader::file()`] or [`load_certs_from_paths()`].
+    Configured,
+}
+
+/// Where a certificate in a [`CertificateResult`] was loaded from.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    /// Where the certificate was first found.
+    pub origin: Origin,
+    /// Which source [`Provenance::origin`] belongs to, if known.
+    pub source: Option<Source>,
+    /// Where identical copies of the certificate were found afterwards.
+    ///
+    /// These copies were removed from the result as duplicates.
+    pub shadowed: Vec<Origin>,
+    /// Metadata parsed from the certificate, or `None` if it could not be parsed.
+    ///
+    /// A certificate that could not be parsed is kept, and reported in
+    /// [`CertificateResult::warnings`] as an [`ErrorKind::MalformedCertificate`].
+    #[cfg(feature = "metadata")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "metadata")))]
+    pub info: Option<Box<CertificateInfo>>,
+}
+
+/// A location a certificate was loaded from.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// A certificate file, or a file in a certificate directory.
+    File {
+        /// The path of the file.
+        path: PathBuf,
+        /// The position of the certificate in the file, starting at 0.
+        index: usize,
+    },
+    /// A file in a tar or zip archive.
+    ArchiveMember {
+        /// The path of the archive.
+        path: PathBuf,
+        /// The name of the file in the archive.
+        member: String,
+        /// The position of the certificate in the file, starting at 0.
+        index: usize,
+    },
+    /// An entry in a keystore file.
+    Keystore {
+        /// The path of the keystore.
+        path: PathBuf,
+        /// The alias of the entry.
+        alias: String,
+    },
+    /// A certificate in an NSS database.
+    NssDatabase {
+        /// The path of the `cert9.db` file.
+        path: PathBuf,
+        /// The certificate's nickname.
+        nickname: String,
+        /// The trust flags, as shown by `certutil -L`; for example `CT,C,C`.
+        trust: String,
+    },
+    /// A root in a published list of trust anchors, such as a CCADB report.
+    RootList {
+        /// The path of the list.
+        path: PathBuf,
+        /// The position of the root in the list, starting at 0.
+        index: usize,
+        /// What the list says about the root.
+        metadata: Box<RootMetadata>,
+    },
+    /// A platform certificate store.
+    Platform {
+        /// The name of the store.
+        store: &'static str,
+    },
+    /// A certificate whose origin was not recorded, such as one added to
+    /// [`CertificateResult::certs`] directly.
+    Unknown,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File { path, index } => {
+                write!(f, "certificate {index} in '{}'", path.display())
+            }
+            Self::ArchiveMember {
+                path,
+                member,
+                index,
+            } => write!(
+                f,
+                "certificate {index} in '{member}' in '{}'",
+                path.display()
+            ),
+            Self::Keystore { path, alias } => {
+                write!(f, "entry '{alias}' in '{}'", path.display())
+            }
+            Self::NssDatabase { path, nickname, .. } => {
+                write!(f, "certificate '{nickname}' in '{}'", path.display())
+            }
+            Self::RootList { path, index, .. } => {
+                write!(f, "root {index} in '{}'", path.display())
+            }
+            Self::Platform { store } => write!(f, "the {store} store"),
+            Self::Unknown => write!(f, "a certificate of unknown origin"),
+        }
+    }
+}
+
+/// Information about a root from a published list of trust anchors.
+///
+/// Fields the list does not provide are empty.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RootMetadata {
+    /// The name of the root, usually its common name.
+    pub name: Option<String>,
+    /// The organization operating the root.
+    pub owner: Option<String>,
+    /// The SHA-256 fingerprint of the certificate, as given by the list.
+    pub sha256_fingerprint: Option<String>,
+    /// The purposes the root is trusted for, for example `Websites` or `Email`.
+    pub trust_bits: Vec<String>,
+    /// The OIDs of the policies for which the root may issue EV certificates.
+    pub ev_policy_oids: Vec<String>,
+    /// The date after which TLS server certificates issued by the root are distrusted.
+    pub tls_distrust_after: Option<SystemTime>,
+    /// Constraints on the root's use; the root is trusted if any one of them is met.
+    pub constraints: Vec<RootConstraints>,
+}
+
+/// A set of constraints on a root, as used by the Chrome Root Store.
+///
+/// All of the constraints in the set must be met.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RootConstraints {
+    /// Only certificates whose SCTs were all issued before this time are trusted.
+    pub sct_not_after: Option<SystemTime>,
+    /// Only certificates whose SCTs were all issued after this time are trusted.
+    pub sct_all_after: Option<SystemTime>,
+    /// The first Chrome version the root is trusted by.
+    pub min_version: Option<String>,
+    /// The first Chrome version the root is no longer trusted by.
+    pub max_version_exclusive: Option<String>,
+    /// The DNS names the root may issue certificates for, if limited.
+    pub permitted_dns_names: Vec<String>,
+    /// Any other constraints, by name, with their values as written.
+    pub other: Vec<(String, String)>,
+}
+
+/// A certificate that was found, but not included in [`CertificateResult::certs`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exclusion {
+    /// The certificate.
+    pub cert: CertificateDer<'static>,
+    /// Where the certificate was found.
+    pub origin: Origin,
+    /// Why the certificate was excluded.
+    pub reason: ExclusionReason,
+}
+
+/// Why a certificate was excluded from a [`CertificateResult`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// The certificate's trust settings do not allow it to authenticate TLS servers.
+    ///
+    /// For example, the `X509_CERT_AUX` data of an OpenSSL `TRUSTED CERTIFICATE`
+    /// rejects `serverAuth`, or lists trusted purposes that don't include it.
+    NotTrustedForServerAuth,
+    /// The certificate is explicitly distrusted, for example by a p11-kit
+    /// `x-distrusted` object or blocklist.
+    Distrusted,
+    /// The certificate is distrusted for TLS server certificates issued after `time`.
+    ///
+    /// rustls cannot limit a trust anchor to certificates issued before a certain
+    /// date, so the certificate is excluded altogether.
+    ServerDistrustAfter {
+        /// The date after which issued certificates are distrusted.
+        time: SystemTime,
+    },
+    /// The certificate was found, but it is not marked as a trust anchor.
+    NotTrusted,
+    /// The certificate expired before the time given to [`Loader::valid_at()`].
+    Expired {
+        /// The end of the certificate's validity period.
+        not_after: SystemTime,
+    },
+    /// The certificate only becomes valid after the time given to
+    /// [`Loader::valid_at()`].
+    NotYetValid {
+        /// The start of the certificate's validity period.
+        not_before: SystemTime,
+    },
+    /// The certificate matches a rule of a [`Blocklist`].
+    Blocked {
+        /// The first rule that matched.
+        rule: BlockRule,
+    },
+    /// The certificate is not a CA certificate, and [`Loader::ca_policy()`] is
+    /// [`CaPolicy::Exclude`].
+    NotCa,
+    /// The certificate's extended key usage extension allows none of the purposes
+    /// given to [`Loader::purposes()`].
+    NotValidForPurpose {
+        /// The purposes the certificate does allow, as dotted object identifiers.
+        allowed: Vec<String>,
+    },
+}
+
 /// Certificate paths from `SSL_CERT_FILE` and/or `SSL_CERT_DIR`.
+#[derive(Default)]
 struct CertPaths {
     file: Option<PathBuf>,
     dirs: Vec<PathBuf>,
 }
 
 impl CertPaths {
-    fn from_env() -> Self {
+    /// Read the paths using `var`, which looks up an environment variable by name.
+    fn from_env(var: impl Fn(&str) -> Option<OsString>) -> Self {
         Self {
-            file: env::var_os(ENV_CERT_FILE).map(PathBuf::from),
+            file: var(ENV_CERT_FILE).map(PathBuf::from),
             // Read `SSL_CERT_DIR`, split it on the platform delimiter (`:` on Unix, `;` on Windows),
             // and return the entries as `PathBuf`s.
             //
             // See <https://docs.openssl.org/3.5/man1/openssl-rehash/#options>
-            dirs: match env::var_os(ENV_CERT_DIR) {
+            dirs: match var(ENV_CERT_DIR) {
                 Some(dirs) => env::split_paths(&dirs)
                     .filter(|p| !p.as_os_str().is_empty())
                     .collect(),
@@ -211,6 +760,10 @@ impl CertPaths {
         }
     }
 
+    fn is_empty(&self) -> bool {
+        self.file.is_none() && self.dirs.is_empty()
+    }
+
     /// Load certificates from the paths.
     ///
     /// See [`load_certs_from_paths()`].
@@ -224,21 +777,60 @@ impl CertPaths {
 /// If both are `None`, returns an empty [`CertificateResult`].
 ///
 /// If `file` is `Some`, it is always used, so it must be a path to an existing,
-/// accessible file from which certificates can be loaded successfully. While parsing,
-/// the rustls-pki-types PEM parser will ignore parts of the file which are
+/// accessible file from which certificates can be loaded successfully. Files
+/// consisting of DER-encoded certificates are loaded as such. Otherwise, the
+/// rustls-pki-types PEM parser will ignore parts of the file which are
 /// not considered part of a certificate. Certificates which are not in the right
-/// format (PEM) or are otherwise corrupted may get ignored silently.
+/// format (PEM) or are otherwise corrupted may get ignored silently. Binary files
+/// that are not DER-encoded certificates produce an error.
 ///
 /// If `dir` is defined, a directory must exist at this path, and all files
 /// contained in it must be loaded successfully, subject to the rules outlined above for `file`.
-/// The directory is not scanned recursively and may be empty.
+/// The directory is not scanned recursively and may be empty; see
+/// [`load_certs_from_dir()`] for other ways of scanning it.
+///
+/// If the `RUSTLS_NATIVE_CERTS_BLOCKLIST` environment variable names a
+/// [blocklist](Blocklist) file, matching certificates are excluded. The variable is
+/// read from the process environment; use a [`Loader`] with
+/// [`Loader::environment()`] to provide it explicitly.
 pub fn load_certs_from_paths(file: Option<&Path>, dir: Option<&Path>) -> CertificateResult {
+    load_certs_from_paths_with_env(file, dir, |name| env::var_os(name))
+}
+
+/// [`load_certs_from_paths()`], reading environment variables using `var`.
+fn load_certs_from_paths_with_env(
+    file: Option<&Path>,
+    dir: Option<&Path>,
+    var: impl Fn(&str) -> Option<OsString>,
+) -> CertificateResult {
     let dir = match dir {
         Some(d) => vec![d],
         None => Vec::new(),
     };
 
-    load_certs_from_paths_internal(file, dir.as_ref())
+    let mut out = CertificateResult::default();
+    out.extend(
+        load_certs_from_paths_internal(file, dir.as_ref()),
+        Some(Source::Configured),
+    );
+
+    blocklist::apply_env(blocklist_from_env(var).as_deref(), &mut out);
+    out
+}
+
+/// Whether `RUSTLS_NATIVE_CERTS_ADDITIVE`, read using `var`, enables additive mode.
+fn additive_from_env(var: impl Fn(&str) -> Option<OsString>) -> bool {
+    match var(ENV_ADDITIVE) {
+        Some(value) => !matches!(value.to_str(), Some("" | "0" | "false")),
+        None => false,
+    }
+}
+
+/// The blocklist file named by `RUSTLS_NATIVE_CERTS_BLOCKLIST`, read using `var`.
+fn blocklist_from_env(var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    var(ENV_BLOCKLIST)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
 }
 
 fn load_certs_from_paths_internal(
@@ -258,83 +850,206 @@ fn load_certs_from_paths_internal(
         load_pem_certs_from_dir(cert_dir.as_ref(), &mut out);
     }
 
-    out.certs
-        .sort_unstable_by(|a, b| a.cmp(b));
-    out.certs.dedup();
+    out.dedup();
     out
 }
 
 /// Load certificate from certificate directory (what OpenSSL calls CAdir)
 fn load_pem_certs_from_dir(dir: &Path, out: &mut CertificateResult) {
-    let dir_reader = match fs::read_dir(dir) {
-        Ok(reader) => reader,
-        Err(err) => {
-            out.io_error(err, dir, "opening directory");
-            return;
-        }
+    scan::scan_dir(dir, &DirOptions::default(), out);
+}
+
+/// Load certificates from a file containing PEM-encoded certificates (including
+/// OpenSSL trusted certificates), one or more DER-encoded certificates, or a
+/// PKCS#7 bundle in PEM or DER form.
+///
+/// With the `archive` feature, the file may also be gzip-compressed.
+fn load_pem_certs(path: &Path, out: &mut CertificateResult) {
+    match fs::read(path) {
+        Ok(data) => load_file_data(data, path, out),
+        Err(err) => out.pem_error(pem::Error::Io(err), path),
+    }
+}
+
+/// Like [`load_pem_certs()`], but with `data` already read from the file at `path`.
+fn load_file_data(data: Vec<u8>, path: &Path, out: &mut CertificateResult) {
+    #[cfg(feature = "archive")]
+    let data = match archive::is_gzip(&data) {
+        true => match archive::gunzip(&data) {
+            Ok(data) => data,
+            Err(error) => return archive::archive_error(out, error, path),
+        },
+        false => data,
     };
 
-    for entry in dir_reader {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(err) => {
-                out.io_error(err, dir, "reading directory entries");
-                continue;
+    load_cert_data(
+        &data,
+        path,
+        |index| Origin::File {
+            path: path.to_owned(),
+            index,
+        },
+        out,
+    );
+}
+
+/// Load certificates from `data`, the contents of a certificate file.
+///
+/// `path` is used in errors, and `origin` gives the origin of each certificate
+/// by its position in `data`.
+fn load_cert_data(
+    data: &[u8],
+    path: &Path,
+    origin: impl Fn(usize) -> Origin,
+    out: &mut CertificateResult,
+) {
+    let errors = out.errors.len();
+    // Each certificate, and whether it was excluded.
+    let mut certs = Vec::new();
+    if let Some(ders) = der::split_certificates(data) {
+        certs.extend(
+            ders.into_iter()
+                .map(|der| (der.to_vec(), None)),
+        );
+    } else if pkcs7::is_pkcs7(data) {
+        load_pkcs7(data, path, &mut certs, out);
+    } else {
+        // Blocks of each kind are found separately, then put back in file order
+        // by the offset at which each one ends.
+        let mut blocks = Vec::new();
+        let mut reader = io::Cursor::new(data);
+        loop {
+            match pem::from_buf(&mut reader) {
+                Ok(Some((SectionKind::Certificate, der))) => {
+                    blocks.push((reader.position() as usize, vec![(der, None)]))
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(err) => out.pem_error(err, path),
             }
-        };
+        }
 
-        let path = entry.path();
+        for (offset, section) in armor::sections_with_offsets(data, "TRUSTED CERTIFICATE") {
+            let der = match section {
+                Ok(der) => der,
+                Err(()) => {
+                    out.trusted_certificate_error(path);
+                    continue;
+                }
+            };
 
-        // `openssl rehash` used to create this directory uses symlinks. So,
-        // make sure we resolve them.
-        let metadata = match fs::metadata(&path) {
-            Ok(metadata) => metadata,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                // Dangling symlink
-                continue;
-            }
-            Err(e) => {
-                out.io_error(e, &path, "failed to open file");
-                continue;
+            match trusted::split(&der) {
+                Ok((cert, aux)) => blocks.push((
+                    offset,
+                    vec![(
+                        cert.to_vec(),
+                        match aux.trusted_for_server_auth() {
+                            true => None,
+                            false => Some(ExclusionReason::NotTrustedForServerAuth),
+                        },
+                    )],
+                )),
+                Err(_) => out.trusted_certificate_error(path),
             }
-        };
+        }
 
-        if metadata.is_file() {
-            load_pem_certs(&path, out);
+        for (offset, section) in armor::sections_with_offsets(data, "PKCS7") {
+            let mut bundle = Vec::new();
+            match section {
+                Ok(der) => load_pkcs7(&der, path, &mut bundle, out),
+                Err(()) => out.pkcs7_error(path),
+            }
+            blocks.push((offset, bundle));
         }
+
+        blocks.sort_by_key(|(offset, _)| *offset);
+        certs.extend(
+            blocks
+                .into_iter()
+                .flat_map(|(_, block)| block),
+        );
     }
-}
 
-fn load_pem_certs(path: &Path, out: &mut CertificateResult) {
-    let iter = match CertificateDer::pem_file_iter(path) {
-        Ok(iter) => iter,
-        Err(err) => {
-            out.pem_error(err, path);
-            return;
-        }
-    };
+    // For compatibility, text files without any certificates are ignored. Binary
+    // files that aren't DER certificates are most likely in some other format.
+    if certs.is_empty() && out.errors.len() == errors && is_binary(data) {
+        out.errors.push(Error {
+            context: "failed to load certificates from file",
+            kind: ErrorKind::UnrecognizedFormat {
+                path: path.to_owned(),
+            },
+            from: None,
+        });
+    }
 
-    for result in iter {
-        match result {
-            Ok(cert) => out.certs.push(cert),
-            Err(err) => out.pem_error(err, path),
+    for (index, (cert, excluded)) in certs.into_iter().enumerate() {
+        let origin = origin(index);
+        match excluded {
+            Some(reason) => out.exclude(CertificateDer::from(cert), origin, reason),
+            None => out.push_cert(CertificateDer::from(cert), origin),
         }
     }
 }
 
+fn load_pkcs7(
+    data: &[u8],
+    path: &Path,
+    certs: &mut Vec<(Vec<u8>, Option<ExclusionReason>)>,
+    out: &mut CertificateResult,
+) {
+    match pkcs7::certificates(data) {
+        Ok(ders) => certs.extend(
+            ders.into_iter()
+                .map(|der| (der.to_vec(), None)),
+        ),
+        Err(_) => out.pkcs7_error(path),
+    }
+}
+
+fn is_binary(data: &[u8]) -> bool {
+    data.iter()
+        .any(|&b| b < 0x20 && !b.is_ascii_whitespace())
+}
+
+#[non_exhaustive]
 #[derive(Debug)]
 pub struct Error {
     pub context: &'static str,
     pub kind: ErrorKind,
+    /// Where the error came from, if known.
+    ///
+    /// This is `None` for errors pushed onto [`CertificateResult::errors`] by
+    /// the caller.
+    pub from: Option<Source>,
 }
 
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        Some(match &self.kind {
-            ErrorKind::Io { inner, .. } => inner,
-            ErrorKind::Os(err) => &**err,
-            ErrorKind::Pem(err) => err,
-        })
+        match &self.kind {
+            ErrorKind::Io { inner, .. } => Some(inner),
+            ErrorKind::Os(err) => Some(&**err),
+            ErrorKind::Pem(err) => Some(err),
+            ErrorKind::UnrecognizedFormat { .. }
+            | ErrorKind::MalformedPkcs7 { .. }
+            | ErrorKind::MalformedTrustedCertificate { .. }
+            | ErrorKind::MalformedP11Kit { .. }
+            | ErrorKind::MalformedCertdata { .. }
+            | ErrorKind::MalformedCcadb { .. }
+            | ErrorKind::MalformedChromeRootStore { .. }
+            | ErrorKind::MalformedBlocklist { .. }
+            | ErrorKind::SymlinkNotAllowed { .. }
+            | ErrorKind::NotCa { .. } => None,
+            #[cfg(feature = "metadata")]
+            ErrorKind::MalformedCertificate { .. } => None,
+            #[cfg(feature = "archive")]
+            ErrorKind::Archive { inner, .. } => Some(inner),
+            #[cfg(feature = "jks")]
+            ErrorKind::Keystore { inner, .. } => Some(inner),
+            #[cfg(feature = "nssdb")]
+            ErrorKind::NssDatabase { inner, .. } => Some(&**inner),
+            #[cfg(feature = "pkcs12")]
+            ErrorKind::Pkcs12 { inner, .. } => Some(inner),
+        }
     }
 }
 
@@ -348,6 +1063,79 @@ impl fmt::Display for Error {
             }
             ErrorKind::Os(err) => err.fmt(f),
             ErrorKind::Pem(err) => err.fmt(f),
+            ErrorKind::UnrecognizedFormat { path } => {
+                write!(f, "neither PEM nor DER at '{}'", path.display())
+            }
+            ErrorKind::MalformedPkcs7 { path } => {
+                write!(f, "malformed PKCS#7 bundle at '{}'", path.display())
+            }
+            ErrorKind::MalformedTrustedCertificate { path } => {
+                write!(
+                    f,
+                    "malformed TRUSTED CERTIFICATE block at '{}'",
+                    path.display()
+                )
+            }
+            ErrorKind::MalformedP11Kit { path, line } => {
+                write!(f, "malformed object on line {line} of '{}'", path.display())
+            }
+            ErrorKind::MalformedCertdata { path, line } => {
+                write!(
+                    f,
+                    "malformed certdata.txt on line {line} of '{}'",
+                    path.display()
+                )
+            }
+            ErrorKind::MalformedCcadb { path, line } => {
+                write!(
+                    f,
+                    "malformed CCADB report on line {line} of '{}'",
+                    path.display()
+                )
+            }
+            ErrorKind::MalformedChromeRootStore { path, line } => {
+                write!(
+                    f,
+                    "malformed Chrome root store on line {line} of '{}'",
+                    path.display()
+                )
+            }
+            ErrorKind::MalformedBlocklist { path, line } => {
+                write!(
+                    f,
+                    "malformed blocklist rule on line {line} of '{}'",
+                    path.display()
+                )
+            }
+            ErrorKind::SymlinkNotAllowed { path, target } => {
+                write!(
+                    f,
+                    "symlink to '{}' not allowed at '{}'",
+                    target.display(),
+                    path.display()
+                )
+            }
+            ErrorKind::NotCa { origin } => write!(f, "{origin}"),
+            #[cfg(feature = "metadata")]
+            ErrorKind::MalformedCertificate { origin } => {
+                write!(f, "malformed {origin}")
+            }
+            #[cfg(feature = "archive")]
+            ErrorKind::Archive { inner, path } => {
+                write!(f, "{inner} at '{}'", path.display())
+            }
+            #[cfg(feature = "jks")]
+            ErrorKind::Keystore { inner, path } => {
+                write!(f, "{inner} at '{}'", path.display())
+            }
+            #[cfg(feature = "nssdb")]
+            ErrorKind::NssDatabase { path, inner } => {
+                write!(f, "{inner} at '{}'", path.display())
+            }
+            #[cfg(feature = "pkcs12")]
+            ErrorKind::Pkcs12 { inner, path } => {
+                write!(f, "{inner} at '{}'", path.display())
+            }
         }
     }
 }
@@ -355,13 +1143,116 @@ impl fmt::Display for Error {
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum ErrorKind {
-    Io { inner: io::Error, path: PathBuf },
+    Io {
+        inner: io::Error,
+        path: PathBuf,
+    },
     Os(Box<dyn StdError + Send + Sync + 'static>),
     Pem(pem::Error),
+    /// A file that is neither PEM nor DER.
+    UnrecognizedFormat {
+        path: PathBuf,
+    },
+    /// A PKCS#7 bundle that could not be parsed.
+    MalformedPkcs7 {
+        path: PathBuf,
+    },
+    /// An OpenSSL `TRUSTED CERTIFICATE` block that could not be parsed.
+    MalformedTrustedCertificate {
+        path: PathBuf,
+    },
+    /// A p11-kit object that could not be parsed.
+    MalformedP11Kit {
+        path: PathBuf,
+        /// The line on which the problem was found, starting at 1.
+        line: usize,
+    },
+    /// A Mozilla `certdata.txt` file that could not be parsed.
+    MalformedCertdata {
+        path: PathBuf,
+        /// The line on which the problem was found, starting at 1.
+        line: usize,
+    },
+    /// A CCADB CSV report that could not be parsed.
+    MalformedCcadb {
+        path: PathBuf,
+        /// The line on which the problem was found, starting at 1.
+        line: usize,
+    },
+    /// A Chrome Root Store `root_store.textproto` that could not be parsed, or that
+    /// refers to a certificate that could not be found.
+    MalformedChromeRootStore {
+        path: PathBuf,
+        /// The line on which the problem was found, starting at 1.
+        line: usize,
+    },
+    /// A [`Blocklist`] file with a rule that could not be parsed.
+    MalformedBlocklist {
+        path: PathBuf,
+        /// The line on which the problem was found, starting at 1.
+        line: usize,
+    },
+    /// A symlink in a scanned directory that points outside the allowed directories.
+    ///
+    /// See [`DirOptions::symlink_containment()`].
+    SymlinkNotAllowed {
+        path: PathBuf,
+        /// Where the symlink points.
+        target: PathBuf,
+    },
+    /// A certificate that is not a CA certificate, kept because
+    /// [`Loader::ca_policy()`] is [`CaPolicy::Warn`].
+    ///
+    /// This is reported in [`CertificateResult::warnings`].
+    NotCa {
+        /// Where the certificate was found.
+        origin: Origin,
+    },
+    /// A certificate that was loaded, but could not be parsed for its
+    /// [metadata](Provenance::info).
+    #[cfg(feature = "metadata")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "metadata")))]
+    MalformedCertificate {
+        /// Where the certificate was found.
+        origin: Origin,
+    },
+    /// A compressed file or archive, or a file in an archive, that could not be read.
+    ///
+    /// For a file in an archive, `path` is the archive's path joined with the
+    /// name of the file.
+    #[cfg(feature = "archive")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "archive")))]
+    Archive {
+        inner: ArchiveError,
+        path: PathBuf,
+    },
+    /// A Java keystore that could not be verified or parsed.
+    #[cfg(feature = "jks")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "jks")))]
+    Keystore {
+        inner: KeystoreError,
+        path: PathBuf,
+    },
+    /// An NSS database that could not be read.
+    #[cfg(feature = "nssdb")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "nssdb")))]
+    NssDatabase {
+        path: PathBuf,
+        inner: Box<dyn StdError + Send + Sync + 'static>,
+    },
+    /// A PKCS#12 file that could not be verified, decrypted or parsed.
+    #[cfg(feature = "pkcs12")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "pkcs12")))]
+    Pkcs12 {
+        inner: Pkcs12Error,
+        path: PathBuf,
+    },
 }
 
 const ENV_CERT_FILE: &str = "SSL_CERT_FILE";
 const ENV_CERT_DIR: &str = "SSL_CERT_DIR";
+const ENV_ADDITIVE: &str = "RUSTLS_NATIVE_CERTS_ADDITIVE";
+const ENV_BLOCKLIST: &str = "RUSTLS_NATIVE_CERTS_BLOCKLIST";
 
 #[cfg(test)]
 mod tests {
@@ -374,6 +1265,15 @@ mod tests {
     #[cfg(unix)]
     use std::os::unix::fs::PermissionsExt;
 
+    use pki_types::pem::PemObject;
+
+    /// The certificates in the PEM file `pem`.
+    pub(crate) fn certs(pem: &[u8]) -> Vec<CertificateDer<'static>> {
+        CertificateDer::pem_slice_iter(pem)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
     #[test]
     fn deduplication() {
         let temp_dir = tempfile::TempDir::new().unwrap();
@@ -424,6 +1324,139 @@ mod tests {
         assert_eq!(result.certs.len(), 2);
     }
 
+    #[test]
+    fn provenance() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("bundle.pem");
+        let dir_path = temp_dir.path().join("certs");
+        fs::create_dir(&dir_path).unwrap();
+        fs::write(
+            &file_path,
+            format!(
+                "{}{}",
+                include_str!("../tests/badssl-com-chain.pem"),
+                include_str!("../integration-tests/one-existing-ca.pem")
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir_path.join("912e7cd5.0"),
+            include_str!("../integration-tests/one-existing-ca.pem"),
+        )
+        .unwrap();
+
+        let result = CertPaths {
+            file: Some(file_path.clone()),
+            dirs: vec![dir_path.clone()],
+        }
+        .load();
+        assert_eq!(result.certs.len(), 2);
+        assert_eq!(result.provenance().count(), 2);
+
+        let globalsign = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        let provenance = result
+            .provenance_of(&globalsign)
+            .unwrap();
+        assert_eq!(
+            provenance.origin,
+            Origin::File {
+                path: file_path,
+                index: 1
+            }
+        );
+        assert_eq!(
+            provenance.shadowed,
+            vec![Origin::File {
+                path: dir_path.join("912e7cd5.0"),
+                index: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn provenance_after_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("bundle.pem");
+        fs::write(
+            &path,
+            format!(
+                "{}{}",
+                include_str!("../tests/badssl-com-chain.pem"),
+                include_str!("../integration-tests/one-existing-ca.pem")
+            ),
+        )
+        .unwrap();
+
+        let mut loaded = CertificateResult::default();
+        load_pem_certs(&path, &mut loaded);
+        load_pem_certs(&temp_dir.path().join("missing.pem"), &mut loaded);
+        let mut result = CertificateResult::default();
+        result.extend(loaded, Some(Source::Environment));
+
+        // Reordering and removing entries doesn't mix up the recorded origins.
+        let globalsign = result.certs.pop().unwrap();
+        result
+            .certs
+            .insert(0, globalsign.clone());
+        result.certs.remove(1);
+        result.errors.insert(
+            0,
+            Error {
+                context: "added by the caller",
+                kind: ErrorKind::Os("test".into()),
+                from: None,
+            },
+        );
+
+        assert_eq!(result.provenance().count(), 1);
+        assert_eq!(
+            result
+                .provenance_of(&globalsign)
+                .unwrap()
+                .origin,
+            Origin::File {
+                path: path.clone(),
+                index: 1
+            }
+        );
+        assert_eq!(
+            result
+                .errors_from(Source::Environment)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn paths_blocklist_from_env() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let blocklist = temp_dir.path().join("blocklist");
+        fs::write(
+            &blocklist,
+            "subject:CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3\n",
+        )
+        .unwrap();
+        let path = Path::new("integration-tests/one-existing-ca.pem");
+
+        let result = load_certs_from_paths_with_env(Some(path), None, |name| {
+            (name == ENV_BLOCKLIST).then(|| blocklist.clone().into())
+        });
+        assert!(result.certs.is_empty());
+        assert!(matches!(
+            result.excluded[..],
+            [Exclusion {
+                reason: ExclusionReason::Blocked { .. },
+                ..
+            }]
+        ));
+
+        let result = load_certs_from_paths_with_env(Some(path), None, |_| None);
+        assert_eq!(result.certs.len(), 1);
+    }
+
     #[test]
     fn malformed_file_from_env() {
         // Certificate parser tries to extract certs from file ignoring
@@ -434,6 +1467,233 @@ mod tests {
         assert!(result.errors.is_empty());
     }
 
+    #[test]
+    fn der_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let badssl =
+            CertificateDer::from_pem_slice(include_bytes!("../tests/badssl-com-chain.pem"))
+                .unwrap();
+        let globalsign = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        fs::write(temp_dir.path().join("badssl.cer"), &badssl).unwrap();
+        fs::write(
+            temp_dir.path().join("both.der"),
+            [badssl.as_ref(), globalsign.as_ref()].concat(),
+        )
+        .unwrap();
+
+        let mut result = CertificateResult::default();
+        load_pem_certs(&temp_dir.path().join("badssl.cer"), &mut result);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.certs, vec![badssl.clone()]);
+
+        let mut result = CertificateResult::default();
+        load_pem_certs_from_dir(temp_dir.path(), &mut result);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.certs.len(), 3);
+        assert!(result.certs.contains(&globalsign));
+    }
+
+    #[cfg(feature = "metadata")]
+    #[test]
+    fn metadata() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("certs.der");
+        let globalsign = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        // The second certificate has the shape of one, but an empty validity period.
+        let malformed = [
+            0x30, 0x0f, 0x30, 0x0d, 0x02, 0x01, 0x01, 0x30, 0x00, 0x30, 0x00, 0x30, 0x00, 0x30,
+            0x00, 0x30, 0x00,
+        ];
+        fs::write(&path, [globalsign.as_ref(), &malformed].concat()).unwrap();
+
+        let result = load_certs_from_paths(Some(&path), None);
+        assert_eq!(result.certs.len(), 2);
+        let info = result
+            .provenance_of(&globalsign)
+            .unwrap()
+            .info
+            .as_ref()
+            .unwrap();
+        assert_eq!(
+            info.subject,
+            "CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3"
+        );
+
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let [Error {
+            kind: ErrorKind::MalformedCertificate { origin },
+            from,
+            ..
+        }] = &result.warnings[..]
+        else {
+            panic!("unexpected warnings {:?}", result.warnings);
+        };
+        assert_eq!(*origin, Origin::File { path, index: 1 });
+        assert_eq!(*from, Some(Source::Configured));
+    }
+
+    #[test]
+    fn block_indices_in_file_order() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("mixed.pem");
+        // The bundle holds the badssl.com certificate followed by GlobalSign.
+        fs::write(
+            &path,
+            format!(
+                "{}{}",
+                include_str!("../tests/bundle-pem.p7b"),
+                include_str!("../tests/badssl-com-chain.pem")
+            ),
+        )
+        .unwrap();
+
+        let mut result = CertificateResult::default();
+        load_pem_certs(&path, &mut result);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let badssl =
+            CertificateDer::from_pem_slice(include_bytes!("../tests/badssl-com-chain.pem"))
+                .unwrap();
+        let origin = |index| Origin::File {
+            path: path.clone(),
+            index,
+        };
+        assert_eq!(result.certs[0], badssl);
+        assert_eq!(result.certs[2], badssl);
+        let provenance = result.provenance_of(&badssl).unwrap();
+        assert_eq!(provenance.origin, origin(0));
+        assert_eq!(provenance.shadowed, [origin(2)]);
+
+        // Excluded trusted certificates are numbered among the others.
+        fs::write(
+            &path,
+            format!(
+                "{}{}",
+                include_str!("../tests/trusted-certs.pem"),
+                include_str!("../integration-tests/one-existing-ca.pem")
+            ),
+        )
+        .unwrap();
+        let mut result = CertificateResult::default();
+        load_pem_certs(&path, &mut result);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let globalsign = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        assert_eq!(result.excluded[0].origin, origin(1));
+        assert_eq!(
+            result
+                .provenance_of(&globalsign)
+                .unwrap()
+                .origin,
+            origin(2)
+        );
+    }
+
+    #[test]
+    fn pkcs7_bundles() {
+        for path in ["tests/bundle-der.p7b", "tests/bundle-pem.p7b"] {
+            let mut result = CertificateResult::default();
+            load_pem_certs(Path::new(path), &mut result);
+            assert!(result.errors.is_empty(), "{path}: {:?}", result.errors);
+            assert_eq!(result.certs.len(), 2, "{path}");
+        }
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let truncated = temp_dir.path().join("truncated.p7b");
+        let data = include_bytes!("../tests/bundle-der.p7b");
+        fs::write(&truncated, &data[..data.len() - 10]).unwrap();
+        let bad_base64 = temp_dir.path().join("bad-base64.p7b");
+        fs::write(
+            &bad_base64,
+            "-----BEGIN PKCS7-----\n!!!!\n-----END PKCS7-----\n",
+        )
+        .unwrap();
+
+        for path in [truncated, bad_base64] {
+            let mut result = CertificateResult::default();
+            load_pem_certs(&path, &mut result);
+            assert!(result.certs.is_empty());
+            match &first_error(&result).kind {
+                ErrorKind::MalformedPkcs7 { path: p } => assert_eq!(p, &path),
+                _ => panic!("unexpected error {:?}", result.errors),
+            }
+        }
+    }
+
+    #[test]
+    fn trusted_certificates() {
+        let path = Path::new("tests/trusted-certs.pem");
+        let mut result = CertificateResult::default();
+        load_pem_certs(path, &mut result);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        // The badssl.com root is trusted for serverAuth; GlobalSign's rejects it.
+        let expected = |pem: &str| CertificateDer::from_pem_slice(pem.as_bytes()).unwrap();
+        assert_eq!(
+            result.certs,
+            [expected(include_str!("../tests/badssl-com-chain.pem"))]
+        );
+        assert_eq!(
+            result.excluded,
+            [Exclusion {
+                cert: expected(include_str!("../integration-tests/one-existing-ca.pem")),
+                origin: Origin::File {
+                    path: path.to_owned(),
+                    index: 1,
+                },
+                reason: ExclusionReason::NotTrustedForServerAuth,
+            }]
+        );
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bad = temp_dir.path().join("bad.pem");
+        fs::write(
+            &bad,
+            "-----BEGIN TRUSTED CERTIFICATE-----\nMAA=\n-----END TRUSTED CERTIFICATE-----\n",
+        )
+        .unwrap();
+        let mut result = CertificateResult::default();
+        load_pem_certs(&bad, &mut result);
+        match &first_error(&result).kind {
+            ErrorKind::MalformedTrustedCertificate { path: p } => assert_eq!(p, &bad),
+            _ => panic!("unexpected error {:?}", result.errors),
+        }
+    }
+
+    #[test]
+    fn unrecognized_binary_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("garbage.bin");
+        fs::write(&path, [0x30, 0x03, 0x02, 0x01, 0x00, 0x00]).unwrap();
+
+        let mut result = CertificateResult::default();
+        load_pem_certs(&path, &mut result);
+        assert!(result.certs.is_empty());
+        match &first_error(&result).kind {
+            ErrorKind::UnrecognizedFormat { path: p } => assert_eq!(p, &path),
+            _ => panic!("unexpected error {:?}", result.errors),
+        }
+
+        // A CRL is a SEQUENCE that starts with a SEQUENCE, like a certificate.
+        let crl = Path::new("tests/crl.der");
+        let mut result = CertificateResult::default();
+        load_pem_certs(crl, &mut result);
+        assert!(result.certs.is_empty());
+        match &first_error(&result).kind {
+            ErrorKind::UnrecognizedFormat { path: p } => assert_eq!(p, crl),
+            _ => panic!("unexpected error {:?}", result.errors),
+        }
+    }
+
     #[test]
     fn from_env_missing_file() {
         let mut result = CertificateResult::default();
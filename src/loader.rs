@@ -0,0 +1,1127 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::{env, fmt};
+
+use pki_types::CertificateDer;
+
+use crate::policy::is_ca;
+#[cfg(feature = "hashed-dir")]
+use crate::HashedDirs;
+use crate::{
+    load_pem_certs, platform, Blocklist, CaPolicy, CertPaths, CertificateResult, DirOptions, Error,
+    ErrorKind, ExclusionReason, KeyPurpose, RootProgram, Source,
+};
+
+/// A configurable certificate loader.
+///
+/// [`load_native_certs()`][crate::load_native_certs] reads its configuration from the
+/// process environment. A `Loader` instead lists its sources explicitly, so that one
+/// process can use several different trust configurations at the same time.
+///
+/// A new `Loader` has no sources. Files and directories added with [`Loader::file()`]
+/// and [`Loader::dir()`] are always loaded. If [`Loader::env()`] is enabled and
+/// `SSL_CERT_FILE` or `SSL_CERT_DIR` is set, certificates are loaded from those
+/// locations and the platform store is skipped, exactly as `load_native_certs()` does.
+/// Otherwise, the platform store is loaded if [`Loader::platform()`] is enabled.
+/// See [`Loader::additive()`] for loading both.
+///
+/// By default, environment variables are read from the process environment. Use
+/// [`Loader::environment()`] to provide them explicitly instead.
+///
+/// ```no_run
+/// use rustls_native_certs::Loader;
+///
+/// let result = Loader::new()
+///     .file("/etc/corp/ca-bundle.pem")
+///     .dir("/etc/corp/certs")
+///     .platform(true)
+///     .load();
+///
+/// for cert in result.expect("could not load certificates") {
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Loader {
+    files: Vec<PathBuf>,
+    dirs: Vec<(PathBuf, DirOptions)>,
+    p11kit: Vec<PathBuf>,
+    certdata: Vec<PathBuf>,
+    ccadb: Vec<(PathBuf, RootProgram)>,
+    chrome_root_stores: Vec<(PathBuf, PathBuf)>,
+    #[cfg(feature = "archive")]
+    archives: Vec<PathBuf>,
+    #[cfg(feature = "nssdb")]
+    nss_dbs: Vec<PathBuf>,
+    #[cfg(feature = "nssdb")]
+    nss_user_db: bool,
+    #[cfg(feature = "jks")]
+    java_keystores: Vec<(PathBuf, Option<String>)>,
+    #[cfg(feature = "pkcs12")]
+    pkcs12: Vec<(PathBuf, String)>,
+    env: bool,
+    environment: Option<HashMap<String, OsString>>,
+    platform: bool,
+    additive: Option<bool>,
+    keep_duplicates: bool,
+    filter: Option<Arc<Filter>>,
+    valid_at: Option<SystemTime>,
+    purposes: Option<Vec<KeyPurpose>>,
+    ca_policy: CaPolicy,
+    blocklist: Blocklist,
+}
+
+impl Loader {
+    /// Create a loader without any sources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load certificates from the file at `path`.
+    ///
+    /// The file follows the same rules as the `file` argument of
+    /// [`load_certs_from_paths()`][crate::load_certs_from_paths]. May be called
+    /// more than once.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files
+            .push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Load certificates from every file in the directory at `path`.
+    ///
+    /// The directory follows the same rules as the `dir` argument of
+    /// [`load_certs_from_paths()`][crate::load_certs_from_paths]. May be called
+    /// more than once.
+    pub fn dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.dirs
+            .push((path.as_ref().to_owned(), DirOptions::default()));
+        self
+    }
+
+    /// Load certificates from the directory at `path`, scanned according to
+    /// `options`.
+    ///
+    /// See [`load_certs_from_dir()`][crate::load_certs_from_dir]. May be called more
+    /// than once.
+    pub fn dir_with_options(mut self, path: impl AsRef<Path>, options: DirOptions) -> Self {
+        self.dirs
+            .push((path.as_ref().to_owned(), options));
+        self
+    }
+
+    /// Load trust anchors from the p11-kit trust source at `path`.
+    ///
+    /// `path` may be a `.p11-kit` file or a trust source directory. Distrust records
+    /// apply across all p11-kit sources of this loader. See
+    /// [`load_certs_from_p11kit()`][crate::load_certs_from_p11kit]. May be called more
+    /// than once.
+    pub fn p11kit(mut self, path: impl AsRef<Path>) -> Self {
+        self.p11kit
+            .push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Load trust anchors from the Mozilla `certdata.txt` file at `path`.
+    ///
+    /// See [`load_certs_from_certdata()`][crate::load_certs_from_certdata]. May be
+    /// called more than once.
+    pub fn certdata(mut self, path: impl AsRef<Path>) -> Self {
+        self.certdata
+            .push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Load the roots trusted for websites by `program` from the CCADB CSV report at
+    /// `path`.
+    ///
+    /// See [`load_certs_from_ccadb()`][crate::load_certs_from_ccadb]. May be called
+    /// more than once.
+    pub fn ccadb(mut self, path: impl AsRef<Path>, program: RootProgram) -> Self {
+        self.ccadb
+            .push((path.as_ref().to_owned(), program));
+        self
+    }
+
+    /// Load the trust anchors from the Chrome Root Store at `textproto`, with their
+    /// certificates from `certs`.
+    ///
+    /// See [`load_certs_from_chrome_root_store()`][crate::load_certs_from_chrome_root_store].
+    /// May be called more than once.
+    pub fn chrome_root_store(
+        mut self,
+        textproto: impl AsRef<Path>,
+        certs: impl AsRef<Path>,
+    ) -> Self {
+        self.chrome_root_stores
+            .push((textproto.as_ref().to_owned(), certs.as_ref().to_owned()));
+        self
+    }
+
+    /// Load certificates from each file in the tar or zip archive at `path`.
+    ///
+    /// See [`load_certs_from_archive()`][crate::load_certs_from_archive]. May be
+    /// called more than once.
+    #[cfg(feature = "archive")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "archive")))]
+    pub fn archive(mut self, path: impl AsRef<Path>) -> Self {
+        self.archives
+            .push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Load trust anchors from the NSS database at `path`.
+    ///
+    /// See [`load_certs_from_nss_db()`][crate::load_certs_from_nss_db]. May be called
+    /// more than once.
+    #[cfg(feature = "nssdb")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "nssdb")))]
+    pub fn nss_db(mut self, path: impl AsRef<Path>) -> Self {
+        self.nss_dbs
+            .push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Whether to load trust anchors from the current user's NSS database,
+    /// `~/.pki/nssdb`, if it exists.
+    ///
+    /// This is where Chromium on Linux, among others, keeps certificates added by
+    /// the user. `HOME` is read from the environment given to
+    /// [`Loader::environment()`], if any. Disabled by default.
+    #[cfg(feature = "nssdb")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "nssdb")))]
+    pub fn nss_user_db(mut self, enabled: bool) -> Self {
+        self.nss_user_db = enabled;
+        self
+    }
+
+    /// Load the trusted certificates from the JKS or JCEKS keystore at `path`.
+    ///
+    /// See [`load_certs_from_java_keystore()`][crate::load_certs_from_java_keystore].
+    /// May be called more than once.
+    #[cfg(feature = "jks")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "jks")))]
+    pub fn java_keystore(mut self, path: impl AsRef<Path>, password: Option<&str>) -> Self {
+        self.java_keystores
+            .push((path.as_ref().to_owned(), password.map(String::from)));
+        self
+    }
+
+    /// Load certificates from the PKCS#12 truststore at `path`, using `password`.
+    ///
+    /// See [`load_certs_from_pkcs12()`][crate::load_certs_from_pkcs12]. May be called
+    /// more than once.
+    #[cfg(feature = "pkcs12")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "pkcs12")))]
+    pub fn pkcs12(mut self, path: impl AsRef<Path>, password: impl Into<String>) -> Self {
+        self.pkcs12
+            .push((path.as_ref().to_owned(), password.into()));
+        self
+    }
+
+    /// Whether to honor the `SSL_CERT_FILE` and `SSL_CERT_DIR` environment variables.
+    ///
+    /// Disabled by default.
+    pub fn env(mut self, enabled: bool) -> Self {
+        self.env = enabled;
+        self
+    }
+
+    /// Read environment variables from `vars` instead of the process environment.
+    ///
+    /// This enables [`Loader::env()`]. Variables that are not in `vars` are treated as
+    /// unset. This allows resolving trust sources deterministically, without reading or
+    /// modifying global state.
+    ///
+    /// ```
+    /// use rustls_native_certs::Loader;
+    ///
+    /// let result = Loader::new()
+    ///     .environment([("SSL_CERT_FILE", "/etc/tenant-a/ca.pem")])
+    ///     .load();
+    /// ```
+    pub fn environment<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<OsString>,
+    {
+        self.env = true;
+        self.environment = Some(
+            vars.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Whether to load certificates from the platform's native certificate store.
+    ///
+    /// Disabled by default.
+    pub fn platform(mut self, enabled: bool) -> Self {
+        self.platform = enabled;
+        self
+    }
+
+    /// Whether certificates from `SSL_CERT_FILE` and `SSL_CERT_DIR` are loaded in
+    /// addition to, rather than instead of, the platform store.
+    ///
+    /// If this is not called and [`Loader::env()`] is enabled, additive mode is
+    /// enabled if the `RUSTLS_NATIVE_CERTS_ADDITIVE` environment variable is set to a
+    /// value other than `0` or `false`. Use [`CertificateResult::errors_from()`] to
+    /// tell errors from the two sources apart.
+    pub fn additive(mut self, enabled: bool) -> Self {
+        self.additive = Some(enabled);
+        self
+    }
+
+    /// Whether to remove duplicate certificates from the result.
+    ///
+    /// Enabled by default. When enabled, the returned certificates are also sorted.
+    pub fn deduplicate(mut self, enabled: bool) -> Self {
+        self.keep_duplicates = !enabled;
+        self
+    }
+
+    /// Only keep certificates for which `filter` returns `true`.
+    ///
+    /// Replaces any previously set filter.
+    pub fn filter(
+        mut self,
+        filter: impl Fn(&CertificateDer<'_>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Exclude certificates that are not valid at `now`.
+    ///
+    /// Applies to all sources. Certificates that have expired or are not yet valid
+    /// are listed in [`CertificateResult::excluded`] as
+    /// [`ExclusionReason::Expired`][crate::ExclusionReason::Expired] or
+    /// [`ExclusionReason::NotYetValid`][crate::ExclusionReason::NotYetValid]. Pass
+    /// `SystemTime::now()` for the current time, or a fixed time for reproducible
+    /// results. By default, certificates are not checked.
+    pub fn valid_at(mut self, now: SystemTime) -> Self {
+        self.valid_at = Some(now);
+        self
+    }
+
+    /// Exclude certificates that may not be used for any of `purposes`.
+    ///
+    /// Applies to all sources, on every platform. A certificate may be used for a
+    /// purpose if it has no extended key usage extension, or if the extension lists
+    /// the purpose or `anyExtendedKeyUsage`. Other certificates are listed in
+    /// [`CertificateResult::excluded`] as
+    /// [`ExclusionReason::NotValidForPurpose`][crate::ExclusionReason::NotValidForPurpose].
+    /// Certificates that can't be parsed are kept.
+    ///
+    /// By default, extended key usage is not checked. The Windows platform store
+    /// always requires [`KeyPurpose::ServerAuth`], as listed by Windows.
+    pub fn purposes(mut self, purposes: impl IntoIterator<Item = KeyPurpose>) -> Self {
+        self.purposes = Some(purposes.into_iter().collect());
+        self
+    }
+
+    /// How to treat certificates that are not CA certificates, such as a server's
+    /// own certificate placed in a certificate directory.
+    ///
+    /// Applies to all sources. See [`CaPolicy`]. Defaults to [`CaPolicy::Ignore`].
+    pub fn ca_policy(mut self, policy: CaPolicy) -> Self {
+        self.ca_policy = policy;
+        self
+    }
+
+    /// Exclude the certificates that match a rule of `blocklist`, from every source.
+    ///
+    /// If [`Loader::env()`] is enabled, the blocklist file named by
+    /// `RUSTLS_NATIVE_CERTS_BLOCKLIST` is applied as well. May be called more than
+    /// once.
+    pub fn blocklist(mut self, blocklist: Blocklist) -> Self {
+        self.blocklist.extend(&blocklist);
+        self
+    }
+
+    /// Load certificates from all configured sources.
+    pub fn load(&self) -> CertificateResult {
+        if !self.env {
+            return self.load_with_env(CertPaths::default(), false);
+        }
+
+        let var = |name: &str| self.var(name);
+        self.load_with_env(CertPaths::from_env(var), crate::additive_from_env(var))
+    }
+
+    /// The hashed directories listed in `SSL_CERT_DIR`, for looking up issuers on
+    /// demand, as [`HashedDirs::from_env()`] does.
+    ///
+    /// The variable is read from the environment given to [`Loader::environment()`],
+    /// if any. Returns `None` if [`Loader::env()`] is disabled or the variable is not set.
+    #[cfg(feature = "hashed-dir")]
+    #[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "hashed-dir")))]
+    pub fn hashed_dirs(&self) -> Option<HashedDirs> {
+        match self.env {
+            true => HashedDirs::from_var(|name| self.var(name)),
+            false => None,
+        }
+    }
+
+    /// Look up an environment variable, in the environment given to
+    /// [`Loader::environment()`] if any.
+    fn var(&self, name: &str) -> Option<OsString> {
+        match &self.environment {
+            Some(vars) => vars.get(name).cloned(),
+            None => env::var_os(name),
+        }
+    }
+
+    fn load_with_env(&self, env: CertPaths, env_additive: bool) -> CertificateResult {
+        let mut configured = CertificateResult::default();
+        for file in &self.files {
+            load_pem_certs(file, &mut configured);
+        }
+
+        for (dir, options) in &self.dirs {
+            crate::scan::scan_dir(dir, options, &mut configured);
+        }
+
+        if !self.p11kit.is_empty() {
+            crate::p11kit::load_p11kit(&self.p11kit, &mut configured);
+        }
+
+        for path in &self.certdata {
+            crate::certdata::load_certdata(path, &mut configured);
+        }
+
+        for (path, program) in &self.ccadb {
+            crate::ccadb::load_ccadb(path, *program, &mut configured);
+        }
+
+        for (textproto, certs) in &self.chrome_root_stores {
+            crate::chrome::load_chrome_root_store(textproto, certs, &mut configured);
+        }
+
+        #[cfg(feature = "archive")]
+        for path in &self.archives {
+            crate::archive::load_archive(path, &mut configured);
+        }
+
+        #[cfg(feature = "nssdb")]
+        for path in &self.nss_dbs {
+            crate::nssdb::load_nss_db(&crate::nssdb::database_file(path), &mut configured);
+        }
+
+        #[cfg(feature = "nssdb")]
+        if self.nss_user_db {
+            if let Some(dir) = crate::nssdb::user_db_from_home(self.var("HOME")) {
+                let path = dir.join("cert9.db");
+                if path.exists() {
+                    crate::nssdb::load_nss_db(&path, &mut configured);
+                }
+            }
+        }
+
+        #[cfg(feature = "jks")]
+        for (path, password) in &self.java_keystores {
+            crate::jks::load_keystore(path, password.as_deref(), &mut configured);
+        }
+
+        #[cfg(feature = "pkcs12")]
+        for (path, password) in &self.pkcs12 {
+            crate::pkcs12::load_pkcs12(path, password, &mut configured);
+        }
+
+        let mut out = CertificateResult::default();
+        out.extend(configured, Some(Source::Configured));
+
+        let additive = self.additive.unwrap_or(env_additive);
+        if !env.is_empty() {
+            out.extend(env.load(), Some(Source::Environment));
+        }
+
+        if self.platform && (env.is_empty() || additive) {
+            out.extend(platform::load_native_certs(), Some(Source::Platform));
+        }
+
+        if let Some(filter) = &self.filter {
+            out.retain(|cert| filter(cert));
+        }
+
+        if !self.keep_duplicates {
+            out.dedup();
+        }
+
+        let blocklist = match self.env {
+            true => crate::blocklist_from_env(|name| self.var(name)),
+            false => None,
+        };
+        crate::blocklist::apply_with_env(&self.blocklist, blocklist.as_deref(), &mut out);
+
+        if let Some(now) = self.valid_at {
+            out.exclude_invalid_at(now);
+        }
+
+        if let Some(purposes) = &self.purposes {
+            out.exclude_where(|cert| crate::policy::check_purposes(cert, purposes));
+        }
+
+        match self.ca_policy {
+            CaPolicy::Ignore => {}
+            CaPolicy::Warn => {
+                let warnings = out
+                    .provenance()
+                    .filter(|(cert, _)| !is_ca(cert))
+                    .map(|(_, provenance)| Error {
+                        context: "trust anchor is not a CA certificate",
+                        kind: ErrorKind::NotCa {
+                            origin: provenance.origin.clone(),
+                        },
+                        from: provenance.source,
+                    })
+                    .collect::<Vec<_>>();
+                out.warnings.extend(warnings);
+            }
+            CaPolicy::Exclude => {
+                out.exclude_where(|cert| (!is_ca(cert)).then_some(ExclusionReason::NotCa))
+            }
+        }
+
+        out
+    }
+}
+
+type Filter = dyn Fn(&CertificateDer<'_>) -> bool + Send + Sync;
+
+impl fmt::Debug for Loader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("Loader");
+        f.field("files", &self.files)
+            .field("dirs", &self.dirs)
+            .field("p11kit", &self.p11kit)
+            .field("certdata", &self.certdata)
+            .field("ccadb", &self.ccadb)
+            .field("chrome_root_stores", &self.chrome_root_stores);
+        #[cfg(feature = "archive")]
+        f.field("archives", &self.archives);
+        #[cfg(feature = "nssdb")]
+        f.field("nss_dbs", &self.nss_dbs)
+            .field("nss_user_db", &self.nss_user_db);
+        // Passwords are deliberately left out.
+        #[cfg(feature = "jks")]
+        f.field(
+            "java_keystores",
+            &self
+                .java_keystores
+                .iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>(),
+        );
+        #[cfg(feature = "pkcs12")]
+        f.field(
+            "pkcs12",
+            &self
+                .pkcs12
+                .iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>(),
+        );
+        f.field("env", &self.env)
+            .field("environment", &self.environment)
+            .field("platform", &self.platform)
+            .field("additive", &self.additive)
+            .field("keep_duplicates", &self.keep_duplicates)
+            .field("filter", &self.filter.is_some())
+            .field("valid_at", &self.valid_at)
+            .field("purposes", &self.purposes)
+            .field("ca_policy", &self.ca_policy)
+            .field("blocklist", &self.blocklist)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::time::Duration;
+
+    use pki_types::pem::PemObject;
+    use sha2::{Digest, Sha256};
+
+    use crate::{BlockRule, Origin};
+
+    #[test]
+    fn multiple_files_and_dirs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir1 = temp_dir.path().join("one");
+        let dir2 = temp_dir.path().join("two");
+        fs::create_dir(&dir1).unwrap();
+        fs::create_dir(&dir2).unwrap();
+
+        let badssl = temp_dir.path().join("badssl.pem");
+        let globalsign = temp_dir.path().join("globalsign.pem");
+        fs::write(&badssl, BADSSL).unwrap();
+        fs::write(&globalsign, GLOBALSIGN).unwrap();
+        fs::write(dir1.join("badssl.pem"), BADSSL).unwrap();
+        fs::write(dir2.join("globalsign.pem"), GLOBALSIGN).unwrap();
+
+        let result = Loader::new()
+            .file(&badssl)
+            .file(&globalsign)
+            .load();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.certs.len(), 2);
+
+        let result = Loader::new()
+            .dir(&dir1)
+            .dir(&dir2)
+            .load();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.certs.len(), 2);
+
+        let result = Loader::new()
+            .file(&badssl)
+            .dir(&dir1)
+            .deduplicate(false)
+            .load();
+        assert_eq!(result.certs.len(), 2);
+        assert_eq!(result.certs[0], result.certs[1]);
+    }
+
+    #[test]
+    fn dir_with_options() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let vendor = temp_dir.path().join("vendor");
+        fs::create_dir_all(vendor.join("a")).unwrap();
+        fs::write(vendor.join("a/ca.pem"), BADSSL).unwrap();
+        fs::write(vendor.join("a/ca.pem.bak"), GLOBALSIGN).unwrap();
+
+        let result = Loader::new().dir(&vendor).load();
+        assert!(result.certs.is_empty());
+        assert_eq!(result.skipped.len(), 1);
+
+        let result = Loader::new()
+            .dir_with_options(
+                &vendor,
+                DirOptions::new()
+                    .max_depth(1)
+                    .extension("pem"),
+            )
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 1);
+        assert_eq!(result.skipped.len(), 1);
+    }
+
+    #[test]
+    fn no_sources() {
+        let result = Loader::new().load();
+        assert!(result.certs.is_empty());
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn filter() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bundle = temp_dir.path().join("bundle.pem");
+        fs::write(&bundle, format!("{BADSSL}{GLOBALSIGN}")).unwrap();
+
+        let globalsign = CertificateDer::from_pem_slice(GLOBALSIGN.as_bytes()).unwrap();
+        let expected = globalsign.clone();
+        let result = Loader::new()
+            .file(&bundle)
+            .filter(move |cert| *cert == globalsign)
+            .load();
+        assert_eq!(result.certs, vec![expected]);
+    }
+
+    #[test]
+    fn valid_at() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bundle = temp_dir.path().join("bundle.pem");
+        fs::write(&bundle, format!("{BADSSL}{GLOBALSIGN}")).unwrap();
+        let badssl = CertificateDer::from_pem_slice(BADSSL.as_bytes()).unwrap();
+        let globalsign = CertificateDer::from_pem_slice(GLOBALSIGN.as_bytes()).unwrap();
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+        // badssl.com: 2021-10-11 to 2023-10-11; GlobalSign: 2009-03-18 to 2029-03-18
+        let result = Loader::new()
+            .file(&bundle)
+            .valid_at(at(1_700_000_000))
+            .load();
+        assert_eq!(result.certs, [globalsign]);
+        let [exclusion] = &result.excluded[..] else {
+            panic!("unexpected exclusions {:?}", result.excluded);
+        };
+        assert_eq!(exclusion.cert, badssl);
+        assert_eq!(
+            exclusion.reason,
+            ExclusionReason::Expired {
+                not_after: at(1_697_054_634)
+            }
+        );
+        assert!(matches!(exclusion.origin, Origin::File { index: 0, .. }));
+
+        let result = Loader::new()
+            .file(&bundle)
+            .valid_at(at(1_200_000_000))
+            .load();
+        assert!(result.certs.is_empty());
+        assert!(result
+            .excluded
+            .iter()
+            .all(|e| matches!(e.reason, ExclusionReason::NotYetValid { .. })));
+
+        let result = Loader::new()
+            .file(&bundle)
+            .valid_at(at(1_650_000_000))
+            .load();
+        assert_eq!(result.certs.len(), 2);
+        assert!(result.excluded.is_empty());
+    }
+
+    #[test]
+    fn purposes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bundle = temp_dir.path().join("bundle.pem");
+        let code_signing = include_str!("../tests/code-signing-ca.pem");
+        fs::write(&bundle, format!("{GLOBALSIGN}{code_signing}")).unwrap();
+        let globalsign = CertificateDer::from_pem_slice(GLOBALSIGN.as_bytes()).unwrap();
+
+        let result = Loader::new()
+            .file(&bundle)
+            .purposes([KeyPurpose::ServerAuth])
+            .load();
+        assert_eq!(result.certs, [globalsign]);
+        let [exclusion] = &result.excluded[..] else {
+            panic!("unexpected exclusions {:?}", result.excluded);
+        };
+        assert_eq!(
+            exclusion.reason,
+            ExclusionReason::NotValidForPurpose {
+                allowed: vec!["1.3.6.1.5.5.7.3.3".into()]
+            }
+        );
+
+        let result = Loader::new()
+            .file(&bundle)
+            .purposes([
+                KeyPurpose::ClientAuth,
+                KeyPurpose::Other("1.3.6.1.5.5.7.3.3".into()),
+            ])
+            .load();
+        assert_eq!(result.certs.len(), 2);
+        assert!(result.excluded.is_empty());
+    }
+
+    #[test]
+    fn ca_policy() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("ca.pem"), GLOBALSIGN).unwrap();
+        fs::write(temp_dir.path().join("server.pem"), BADSSL).unwrap();
+        let globalsign = CertificateDer::from_pem_slice(GLOBALSIGN.as_bytes()).unwrap();
+        let server = CertificateDer::from_pem_slice(BADSSL.as_bytes()).unwrap();
+        let origin = Origin::File {
+            path: temp_dir.path().join("server.pem"),
+            index: 0,
+        };
+
+        let result = Loader::new()
+            .dir(temp_dir.path())
+            .load();
+        assert_eq!(result.certs.len(), 2);
+        assert!(result.errors.is_empty());
+
+        let result = Loader::new()
+            .dir(temp_dir.path())
+            .ca_policy(CaPolicy::Warn)
+            .load();
+        assert_eq!(result.certs.len(), 2);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let [warning] = &result.warnings[..] else {
+            panic!("unexpected warnings {:?}", result.warnings);
+        };
+        assert_eq!(
+            warning.to_string(),
+            format!(
+                "trust anchor is not a CA certificate: certificate 0 in '{}'",
+                temp_dir
+                    .path()
+                    .join("server.pem")
+                    .display()
+            )
+        );
+        assert!(matches!(&warning.kind, ErrorKind::NotCa { origin: o } if *o == origin));
+        assert_eq!(warning.from, Some(Source::Configured));
+        assert_eq!(
+            result
+                .provenance_of(&server)
+                .unwrap()
+                .source,
+            Some(Source::Configured)
+        );
+
+        let result = Loader::new()
+            .dir(temp_dir.path())
+            .ca_policy(CaPolicy::Exclude)
+            .load();
+        assert_eq!(result.certs, [globalsign]);
+        assert!(result.errors.is_empty());
+        let [exclusion] = &result.excluded[..] else {
+            panic!("unexpected exclusions {:?}", result.excluded);
+        };
+        assert_eq!(exclusion.reason, ExclusionReason::NotCa);
+        assert_eq!(exclusion.origin, origin);
+    }
+
+    #[test]
+    fn blocklist() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bundle = temp_dir.path().join("bundle.pem");
+        fs::write(&bundle, format!("{GLOBALSIGN}{BADSSL}")).unwrap();
+        let blocklist = temp_dir.path().join("blocklist");
+        fs::write(
+            &blocklist,
+            "subject:CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3\n",
+        )
+        .unwrap();
+        let badssl = CertificateDer::from_pem_slice(BADSSL.as_bytes()).unwrap();
+
+        let result = Loader::new()
+            .environment([
+                ("SSL_CERT_FILE", &bundle),
+                ("RUSTLS_NATIVE_CERTS_BLOCKLIST", &blocklist),
+            ])
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs, std::slice::from_ref(&badssl));
+        let [exclusion] = &result.excluded[..] else {
+            panic!("unexpected exclusions {:?}", result.excluded);
+        };
+        assert_eq!(
+            exclusion.origin,
+            Origin::File {
+                path: bundle.clone(),
+                index: 0
+            }
+        );
+        let ExclusionReason::Blocked { rule } = &exclusion.reason else {
+            panic!("unexpected reason {:?}", exclusion.reason);
+        };
+        assert_eq!(
+            rule.to_string(),
+            "subject:CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3"
+        );
+
+        // Rules given to the loader apply to every source, along with the file.
+        let result = Loader::new()
+            .environment([("RUSTLS_NATIVE_CERTS_BLOCKLIST", &blocklist)])
+            .file(&bundle)
+            .blocklist(Blocklist::new().rule(BlockRule::Sha256(Sha256::digest(&badssl).into())))
+            .load();
+        assert!(result.certs.is_empty());
+        assert_eq!(result.excluded.len(), 2);
+
+        // The environment is not read unless enabled.
+        let result = Loader::new().file(&bundle).load();
+        assert_eq!(result.certs.len(), 2);
+
+        // A malformed line is reported, and the other rules still apply.
+        fs::write(
+            &blocklist,
+            "CN=GlobalSign\nsubject:CN=GlobalSign,O=GlobalSign,OU=GlobalSign Root CA - R3\n",
+        )
+        .unwrap();
+        let result = Loader::new()
+            .environment([("RUSTLS_NATIVE_CERTS_BLOCKLIST", &blocklist)])
+            .file(&bundle)
+            .load();
+        assert_eq!(result.certs, [badssl]);
+        assert_eq!(result.excluded.len(), 1);
+        let [error] = &result.errors[..] else {
+            panic!("unexpected errors {:?}", result.errors);
+        };
+        assert!(matches!(
+            error.kind,
+            ErrorKind::MalformedBlocklist { line: 1, .. }
+        ));
+        assert_eq!(
+            result
+                .errors_from(Source::Environment)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn errors_from_all_sources() {
+        let result = Loader::new()
+            .file("no/such/file")
+            .dir("no/such/directory")
+            .load();
+        assert!(result.certs.is_empty());
+        assert_eq!(result.errors.len(), 2);
+    }
+
+    #[test]
+    fn p11kit() {
+        let result = Loader::new()
+            .p11kit("tests/p11-kit/trust.p11-kit")
+            .p11kit("tests/p11-kit")
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 2);
+        assert!(!result.excluded.is_empty());
+    }
+
+    #[test]
+    fn certdata() {
+        let result = Loader::new()
+            .certdata("tests/certdata.txt")
+            .certdata("no/such/certdata.txt")
+            .load();
+        assert_eq!(result.certs.len(), 1);
+        assert_eq!(result.excluded.len(), 4);
+        assert_eq!(
+            result
+                .errors_from(Source::Configured)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn ccadb() {
+        let result = Loader::new()
+            .ccadb("tests/ccadb.csv", RootProgram::Mozilla)
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 1);
+        assert_eq!(result.excluded.len(), 2);
+    }
+
+    #[test]
+    fn chrome_root_store() {
+        let result = Loader::new()
+            .chrome_root_store(
+                "tests/chrome-root-store/root_store.textproto",
+                "tests/chrome-root-store/root_store.certs",
+            )
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 3);
+        assert_eq!(result.excluded.len(), 1);
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn archive() {
+        use std::io::Write;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bundle = temp_dir.path().join("ca-bundle.pem.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder
+            .write_all(include_bytes!("../tests/badssl-com-chain.pem"))
+            .unwrap();
+        fs::write(&bundle, encoder.finish().unwrap()).unwrap();
+
+        let result = Loader::new()
+            .file(&bundle)
+            .archive("tests/certs.zip")
+            .load();
+        assert_eq!(result.certs.len(), 2);
+        // `certs/junk.der` in the archive
+        assert_eq!(result.errors.len(), 1);
+    }
+
+    #[cfg(feature = "nssdb")]
+    #[test]
+    fn nss_db() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let user_db = temp_dir.path().join(".pki/nssdb");
+        fs::create_dir_all(&user_db).unwrap();
+        fs::copy("tests/nssdb/cert9.db", user_db.join("cert9.db")).unwrap();
+
+        let result = Loader::new()
+            .environment([("HOME", temp_dir.path())])
+            .nss_user_db(true)
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 2);
+
+        // A missing user database is not an error.
+        let result = Loader::new()
+            .environment([("HOME", temp_dir.path().join("nobody"))])
+            .nss_user_db(true)
+            .nss_db("tests/nssdb")
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 2);
+    }
+
+    #[cfg(feature = "jks")]
+    #[test]
+    fn java_keystore() {
+        let result = Loader::new()
+            .java_keystore("tests/keystore.jks", Some("changeit"))
+            .java_keystore("tests/keystore.jceks", None)
+            .load();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), 2);
+        assert_eq!(
+            result
+                .provenance()
+                .map(|(_, provenance)| provenance.shadowed.len())
+                .sum::<usize>(),
+            2
+        );
+    }
+
+    #[cfg(feature = "pkcs12")]
+    #[test]
+    fn pkcs12() {
+        let loader = Loader::new()
+            .pkcs12("tests/truststore.p12", "changeit")
+            .pkcs12("tests/truststore-java.p12", "wrong");
+        assert!(!format!("{loader:?}").contains("changeit"));
+
+        let result = loader.load();
+        assert_eq!(result.certs.len(), 2);
+        assert_eq!(
+            result
+                .errors_from(Source::Configured)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn additive() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let globalsign = temp_dir.path().join("globalsign.pem");
+        fs::write(&globalsign, GLOBALSIGN).unwrap();
+
+        let platform = platform::load_native_certs();
+        let env = || CertPaths {
+            file: Some(globalsign.clone()),
+            dirs: vec![temp_dir.path().join("missing")],
+        };
+
+        let loader = Loader::new().platform(true);
+        let result = loader.load_with_env(env(), false);
+        assert_eq!(result.certs.len(), 1);
+        assert_eq!(
+            result
+                .errors_from(Source::Environment)
+                .count(),
+            1
+        );
+        assert_eq!(
+            result
+                .errors_from(Source::Platform)
+                .count(),
+            0
+        );
+
+        let result = loader.load_with_env(env(), true);
+        assert!(result.certs.len() >= platform.certs.len());
+        assert!(result
+            .certs
+            .contains(&CertificateDer::from_pem_slice(GLOBALSIGN.as_bytes()).unwrap()));
+        assert_eq!(
+            result
+                .errors_from(Source::Environment)
+                .count(),
+            1
+        );
+
+        // An explicit setting overrides `RUSTLS_NATIVE_CERTS_ADDITIVE`.
+        let result = loader
+            .clone()
+            .additive(false)
+            .load_with_env(env(), true);
+        assert_eq!(result.certs.len(), 1);
+    }
+
+    #[test]
+    fn environment() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let globalsign = temp_dir.path().join("globalsign.pem");
+        fs::write(&globalsign, GLOBALSIGN).unwrap();
+        fs::write(temp_dir.path().join("badssl.pem"), BADSSL).unwrap();
+
+        let result = Loader::new()
+            .environment([("SSL_CERT_FILE", &globalsign)])
+            .platform(true)
+            .load();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.certs.len(), 1);
+
+        let result = Loader::new()
+            .environment([("SSL_CERT_DIR", temp_dir.path())])
+            .load();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.certs.len(), 2);
+
+        // An empty environment falls back to the platform store.
+        let empty: [(&str, &str); 0] = [];
+        let result = Loader::new()
+            .environment(empty)
+            .platform(true)
+            .load();
+        assert_eq!(
+            result.certs.len(),
+            platform::load_native_certs()
+                .certs
+                .len()
+        );
+
+        let result = Loader::new()
+            .environment([
+                ("SSL_CERT_FILE", globalsign.as_os_str()),
+                ("RUSTLS_NATIVE_CERTS_ADDITIVE", "1".as_ref()),
+            ])
+            .platform(true)
+            .load();
+        assert!(result.certs.len() > 1);
+    }
+
+    #[test]
+    fn merge() {
+        let mut result = Loader::new()
+            .file("no/such/file")
+            .load();
+        let mut other = CertificateResult::default();
+        other
+            .certs
+            .push(CertificateDer::from_pem_slice(BADSSL.as_bytes()).unwrap());
+        other
+            .certs
+            .push(CertificateDer::from_pem_slice(GLOBALSIGN.as_bytes()).unwrap());
+        other
+            .certs
+            .push(CertificateDer::from_pem_slice(BADSSL.as_bytes()).unwrap());
+        other.errors.push(Error {
+            context: "test",
+            kind: crate::ErrorKind::Os("test".into()),
+            from: None,
+        });
+
+        result.merge(other);
+        assert_eq!(result.certs.len(), 2);
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(
+            result
+                .errors_from(Source::Configured)
+                .count(),
+            1
+        );
+    }
+
+    const BADSSL: &str = include_str!("../tests/badssl-com-chain.pem");
+    const GLOBALSIGN: &str = include_str!("../integration-tests/one-existing-ca.pem");
+}
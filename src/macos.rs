@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use pki_types::CertificateDer;
 use security_framework::trust_settings::{Domain, TrustSettings, TrustSettingsForCertificate};
 
-use super::CertificateResult;
+use super::{CertificateResult, Origin};
 
 pub fn load_native_certs() -> CertificateResult {
     // The various domains are designed to interact like this:
@@ -53,18 +53,24 @@ pub fn load_native_certs() -> CertificateResult {
                 }
             };
 
-            all_certs.entry(der).or_insert(trusted);
+            all_certs
+                .entry(der)
+                .or_insert((trusted, *domain));
         }
     }
 
     // Now we have all the certificates and an idea of whether
     // to use them.
-    for (der, trusted) in all_certs.drain() {
+    for (der, (trusted, domain)) in all_certs.drain() {
         use TrustSettingsForCertificate::*;
         if let TrustRoot | TrustAsRoot = trusted {
-            result
-                .certs
-                .push(CertificateDer::from(der));
+            let store = match domain {
+                Domain::User => "user trust settings",
+                Domain::Admin => "admin trust settings",
+                Domain::System => "system trust settings",
+            };
+
+            result.push_cert(CertificateDer::from(der), Origin::Platform { store });
         }
     }
 
@@ -0,0 +1,295 @@
+//! Certificates from NSS shared databases (`cert9.db`), as used by Firefox,
+//! Chromium on Linux, and `certutil`.
+//!
+//! The database is an SQLite file with a single `nssPublic` table. Each row is a
+//! PKCS#11 object, with one column per attribute type, named `a` followed by the
+//! type in hexadecimal. Integer values are stored as 4-byte big-endian blobs.
+//!
+//! As in `certdata.txt`, the trust settings for a certificate are kept in a
+//! separate trust object, which refers to the certificate by issuer and serial
+//! number.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use pki_types::CertificateDer;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{CertificateResult, Error, ErrorKind, ExclusionReason, Origin, Source};
+
+/// Load the trust anchors from the NSS database at `path`.
+///
+/// `path` may be the database directory, with or without the `sql:` prefix, or
+/// the `cert9.db` file itself. The older Berkeley DB format (`cert8.db`) is not
+/// supported.
+///
+/// Certificates trusted to issue server certificates (`C` in the first field of
+/// the trust flags shown by `certutil -L`) are trust anchors. All other
+/// certificates are listed in [`CertificateResult::excluded`]. The nickname and
+/// trust flags of every certificate are available from its
+/// [`Origin::NssDatabase`].
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "nssdb")))]
+pub fn load_certs_from_nss_db(path: impl AsRef<Path>) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    load_nss_db(&database_file(path.as_ref()), &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+/// The location of the current user's NSS database, `~/.pki/nssdb`.
+///
+/// Returns `None` if the `HOME` environment variable is not set. The database
+/// does not necessarily exist.
+///
+/// This reads the process environment. [`Loader::nss_user_db()`][crate::Loader::nss_user_db]
+/// reads `HOME` from the loader's configured environment instead.
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "nssdb")))]
+pub fn user_nss_db() -> Option<PathBuf> {
+    user_db_from_home(env::var_os("HOME"))
+}
+
+pub(crate) fn user_db_from_home(home: Option<OsString>) -> Option<PathBuf> {
+    Some(PathBuf::from(home?).join(".pki/nssdb"))
+}
+
+/// Resolve a database path in any of the forms accepted by NSS to the `cert9.db` file.
+pub(crate) fn database_file(path: &Path) -> PathBuf {
+    let path = match path.to_str() {
+        Some(s) => Path::new(s.strip_prefix("sql:").unwrap_or(s)),
+        None => path,
+    };
+
+    match path.is_dir() {
+        true => path.join("cert9.db"),
+        false => path.to_owned(),
+    }
+}
+
+pub(crate) fn load_nss_db(path: &Path, out: &mut CertificateResult) {
+    if let Err(err) = read(path, out) {
+        out.errors.push(Error {
+            context: "failed to read NSS database",
+            kind: ErrorKind::NssDatabase {
+                path: path.to_owned(),
+                inner: Box::new(err),
+            },
+            from: None,
+        });
+    }
+}
+
+fn read(path: &Path, out: &mut CertificateResult) -> Result<(), rusqlite::Error> {
+    // Don't let SQLite create a database that doesn't exist yet.
+    if let Err(err) = path.metadata() {
+        out.io_error(err, path, "failed to open NSS database");
+        return Ok(());
+    }
+
+    let db = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut statement = db.prepare(&format!(
+        "SELECT a{CKA_CLASS:x}, a{CKA_LABEL:x}, a{CKA_VALUE:x}, a{CKA_ISSUER:x}, \
+         a{CKA_SERIAL_NUMBER:x}, a{CKA_TRUST_SERVER_AUTH:x}, a{CKA_TRUST_CLIENT_AUTH:x}, \
+         a{CKA_TRUST_EMAIL_PROTECTION:x}, a{CKA_TRUST_CODE_SIGNING:x} \
+         FROM nssPublic ORDER BY id"
+    ))?;
+
+    let mut certs = Vec::new();
+    let mut trust = HashMap::new();
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let column = |index| {
+            row.get_ref(index)
+                .and_then(|value| Ok(value.as_bytes_or_null()?))
+                .map(|value| match value {
+                    Some(EXPLICIT_NULL) => Some(Vec::new()),
+                    value => value.map(<[u8]>::to_vec),
+                })
+        };
+
+        let issuer_serial = (column(3)?, column(4)?);
+        match column(0)?.as_deref().and_then(ulong) {
+            Some(CKO_CERTIFICATE) => {
+                if let Some(value) = column(2)? {
+                    certs.push((column(1)?, value, issuer_serial));
+                }
+            }
+            Some(CKO_NSS_TRUST) => {
+                let usages = [column(5)?, column(6)?, column(7)?, column(8)?]
+                    .map(|value| value.as_deref().and_then(ulong));
+                trust.insert(issuer_serial, usages);
+            }
+            _ => {}
+        }
+    }
+
+    for (label, value, issuer_serial) in certs {
+        let [server, client, email, code] = trust
+            .get(&issuer_serial)
+            .copied()
+            .unwrap_or_default();
+        let origin = Origin::NssDatabase {
+            path: path.to_owned(),
+            nickname: label
+                .map(|label| {
+                    String::from_utf8_lossy(&label)
+                        .trim_end_matches('\0')
+                        .to_owned()
+                })
+                .unwrap_or_default(),
+            trust: format!(
+                "{}{},{},{}",
+                flag(server),
+                match client {
+                    Some(CKT_NSS_TRUSTED_DELEGATOR) => "T",
+                    _ => "",
+                },
+                flag(email),
+                flag(code)
+            ),
+        };
+
+        let reason = match server {
+            Some(CKT_NSS_TRUSTED_DELEGATOR) => None,
+            Some(CKT_NSS_NOT_TRUSTED) => Some(ExclusionReason::Distrusted),
+            _ if !trust.contains_key(&issuer_serial) => Some(ExclusionReason::NotTrusted),
+            _ => Some(ExclusionReason::NotTrustedForServerAuth),
+        };
+
+        let cert = CertificateDer::from(value);
+        match reason {
+            Some(reason) => out.exclude(cert, origin, reason),
+            None => out.push_cert(cert, origin),
+        }
+    }
+
+    Ok(())
+}
+
+/// The `certutil` trust flag for a trust value.
+fn flag(trust: Option<u32>) -> &'static str {
+    match trust {
+        Some(CKT_NSS_TRUSTED_DELEGATOR) => "C",
+        Some(CKT_NSS_VALID_DELEGATOR) => "c",
+        Some(CKT_NSS_TRUSTED) => "P",
+        Some(CKT_NSS_NOT_TRUSTED) => "p",
+        _ => "",
+    }
+}
+
+fn ulong(value: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(value.try_into().ok()?))
+}
+
+/// How NSS stores empty attribute values.
+const EXPLICIT_NULL: &[u8] = &[0xa5, 0x00, 0x5a];
+
+const CKA_CLASS: u32 = 0x0;
+const CKA_LABEL: u32 = 0x3;
+const CKA_VALUE: u32 = 0x11;
+const CKA_ISSUER: u32 = 0x81;
+const CKA_SERIAL_NUMBER: u32 = 0x82;
+
+// NSS's vendor-defined values, `CKx_VENDOR_DEFINED | NSSCK_VENDOR_NSS`.
+const CKA_NSS: u32 = 0xce53_4350;
+const CKO_NSS: u32 = 0xce53_4350;
+const CKT_NSS: u32 = 0xce53_4350;
+
+const CKA_TRUST: u32 = CKA_NSS + 0x2000;
+const CKA_TRUST_SERVER_AUTH: u32 = CKA_TRUST + 8;
+const CKA_TRUST_CLIENT_AUTH: u32 = CKA_TRUST + 9;
+const CKA_TRUST_CODE_SIGNING: u32 = CKA_TRUST + 10;
+const CKA_TRUST_EMAIL_PROTECTION: u32 = CKA_TRUST + 11;
+
+const CKO_CERTIFICATE: u32 = 0x1;
+const CKO_NSS_TRUST: u32 = CKO_NSS + 3;
+
+const CKT_NSS_TRUSTED: u32 = CKT_NSS + 1;
+const CKT_NSS_TRUSTED_DELEGATOR: u32 = CKT_NSS + 2;
+const CKT_NSS_NOT_TRUSTED: u32 = CKT_NSS + 10;
+const CKT_NSS_VALID_DELEGATOR: u32 = CKT_NSS + 11;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trust_flags() {
+        let result = load_certs_from_nss_db("sql:tests/nssdb");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let mut entries = result
+            .provenance()
+            .map(|(_, provenance)| (&provenance.origin, None))
+            .chain(
+                result
+                    .excluded
+                    .iter()
+                    .map(|exclusion| (&exclusion.origin, Some(exclusion.reason.clone()))),
+            )
+            .map(|(origin, reason)| match origin {
+                Origin::NssDatabase {
+                    path,
+                    nickname,
+                    trust,
+                } => {
+                    assert_eq!(path, Path::new("tests/nssdb/cert9.db"));
+                    (nickname.as_str(), trust.as_str(), reason)
+                }
+                origin => panic!("unexpected origin {origin:?}"),
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(nickname, ..)| *nickname);
+
+        // Imported with these trust flags, as with `certutil -A -t <trust>`
+        assert_eq!(
+            entries,
+            [
+                ("BadSSL Root", "C,,", None),
+                ("GlobalSign Root CA - R3", "CT,C,C", None),
+                (
+                    "Test Root A",
+                    ",C,",
+                    Some(ExclusionReason::NotTrustedForServerAuth)
+                ),
+                ("Test Root D", "p,p,p", Some(ExclusionReason::Distrusted)),
+                ("Test Root F", ",,", Some(ExclusionReason::NotTrusted)),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_database() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let result = load_certs_from_nss_db(temp_dir.path());
+        assert!(matches!(result.errors[0].kind, ErrorKind::Io { .. }));
+        // No database was created.
+        assert!(!temp_dir
+            .path()
+            .join("cert9.db")
+            .exists());
+
+        let not_sqlite = temp_dir.path().join("cert9.db");
+        std::fs::write(&not_sqlite, "not a database").unwrap();
+        let result = load_certs_from_nss_db(&not_sqlite);
+        assert!(matches!(
+            result.errors[0].kind,
+            ErrorKind::NssDatabase { .. }
+        ));
+    }
+
+    #[test]
+    fn user_db() {
+        assert_eq!(
+            user_db_from_home(Some("/home/user".into())),
+            Some(PathBuf::from("/home/user/.pki/nssdb"))
+        );
+        assert_eq!(user_db_from_home(None), None);
+    }
+}
@@ -0,0 +1,598 @@
+//! Trust policy from p11-kit trust sources, such as `/usr/share/pki/ca-trust-source`
+//! and `/etc/pki/ca-trust/source` on Fedora and RHEL.
+//!
+//! `.p11-kit` files describe PKCS#11 objects in p11-kit's persistent format:
+//!
+//! ```text
+//! [p11-kit-object-v1]
+//! class: certificate
+//! label: "Example Root CA"
+//! trusted: true
+//! nss-server-distrust-after: "%32%34%31%31%33%30%32%33%35%39%35%39%5A"
+//! -----BEGIN CERTIFICATE-----
+//! ...
+//! -----END CERTIFICATE-----
+//! ```
+//!
+//! Besides certificates, these files may contain distrusted public keys and
+//! certificate extensions "stapled" to a public key, which restrict how the
+//! certificates with that key may be used.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use pki_types::CertificateDer;
+
+use crate::der::{self, Reader, SEQUENCE};
+use crate::{
+    armor, load_pem_certs, load_pem_certs_from_dir, x509, CertificateResult, Error, ErrorKind,
+    ExclusionReason, Origin, Source,
+};
+
+/// Load the trust anchors from p11-kit trust sources at `paths`.
+///
+/// Each path may be a `.p11-kit` file or a trust source directory. In a directory,
+/// the `.p11-kit` files are read, certificates in the `anchors` subdirectory are
+/// trusted, and certificates in the `blocklist` (or `blacklist`) subdirectory are
+/// distrusted. Other files in the top-level directory are read like any other
+/// certificate file, so OpenSSL trusted certificates there keep their own trust
+/// settings, and the remaining certificates in them are trusted.
+///
+/// Distrust applies across all `paths`. Certificates that are not trust anchors
+/// after that are listed in [`CertificateResult::excluded`], including:
+///
+/// - Certificates that are distrusted (`x-distrusted`), listed in a blocklist, or
+///   whose public key is distrusted.
+/// - Certificates with a `nss-server-distrust-after` date. rustls cannot limit a
+///   trust anchor to certificates issued before a certain date, so these are
+///   excluded altogether.
+/// - Certificates with a stapled extended key usage extension that does not
+///   include `serverAuth`.
+/// - Certificates that are not marked as trusted.
+///
+/// ```no_run
+/// let result = rustls_native_certs::load_certs_from_p11kit([
+///     "/usr/share/pki/ca-trust-source",
+///     "/etc/pki/ca-trust/source",
+/// ]);
+/// ```
+pub fn load_certs_from_p11kit<P: AsRef<Path>>(
+    paths: impl IntoIterator<Item = P>,
+) -> CertificateResult {
+    let paths = paths
+        .into_iter()
+        .map(|path| path.as_ref().to_owned())
+        .collect::<Vec<_>>();
+
+    let mut loaded = CertificateResult::default();
+    load_p11kit(&paths, &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+pub(crate) fn load_p11kit(paths: &[PathBuf], out: &mut CertificateResult) {
+    let mut policy = Policy::default();
+    for path in paths {
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => policy.load_dir(path, out),
+            Ok(_) => policy.load_file(path, out),
+            Err(err) => out.io_error(err, path, "failed to open p11-kit trust source"),
+        }
+    }
+
+    policy.apply(out);
+}
+
+/// Everything read from the trust sources, before distrust is applied.
+#[derive(Default)]
+struct Policy {
+    certs: Vec<Entry>,
+    distrusted_certs: HashSet<Vec<u8>>,
+    distrusted_keys: HashSet<Vec<u8>>,
+    /// Stapled extended key usages, by public key.
+    purposes: HashMap<Vec<u8>, Vec<Vec<u8>>>,
+}
+
+impl Policy {
+    fn load_dir(&mut self, dir: &Path, out: &mut CertificateResult) {
+        let mut files = match fs::read_dir(dir).and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+        }) {
+            Ok(files) => files,
+            Err(err) => {
+                out.io_error(err, dir, "failed to read p11-kit trust source directory");
+                return;
+            }
+        };
+
+        files.sort();
+        for path in files {
+            if !path.is_file() {
+                continue;
+            }
+
+            match path
+                .extension()
+                .is_some_and(|ext| ext == "p11-kit")
+            {
+                true => self.load_file(&path, out),
+                false => {
+                    let mut loaded = CertificateResult::default();
+                    load_pem_certs(&path, &mut loaded);
+                    self.push_loaded(loaded, Trust::Anchor, out);
+                }
+            }
+        }
+
+        for (subdir, trust) in [
+            ("anchors", Trust::Anchor),
+            ("blocklist", Trust::Distrusted),
+            ("blacklist", Trust::Distrusted),
+        ] {
+            let subdir = dir.join(subdir);
+            if !subdir.is_dir() {
+                continue;
+            }
+
+            let mut loaded = CertificateResult::default();
+            load_pem_certs_from_dir(&subdir, &mut loaded);
+            self.push_loaded(loaded, trust, out);
+        }
+    }
+
+    /// Add the certificates in `loaded` with the given `trust`, and pass
+    /// everything else in it, such as errors, on to `out`.
+    fn push_loaded(
+        &mut self,
+        mut loaded: CertificateResult,
+        trust: Trust,
+        out: &mut CertificateResult,
+    ) {
+        for (cert, provenance) in loaded.provenance() {
+            self.push(cert.to_vec(), provenance.origin.clone(), trust, None);
+        }
+
+        loaded.retain(|_| false);
+        out.extend(loaded, None);
+    }
+
+    fn load_file(&mut self, path: &Path, out: &mut CertificateResult) {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                out.io_error(err, path, "failed to read p11-kit file");
+                return;
+            }
+        };
+
+        let text = String::from_utf8_lossy(&data);
+        let mut index = 0;
+        for object in objects(&text) {
+            let object = match object {
+                Ok(object) => object,
+                Err(line) => {
+                    out.errors.push(Error {
+                        context: "failed to read p11-kit file",
+                        kind: ErrorKind::MalformedP11Kit {
+                            path: path.to_owned(),
+                            line,
+                        },
+                        from: None,
+                    });
+                    continue;
+                }
+            };
+
+            if let Some(cert) = object.cert {
+                let origin = Origin::File {
+                    path: path.to_owned(),
+                    index,
+                };
+                index += 1;
+                let trust = match (object.distrusted, object.trusted) {
+                    (true, _) => Trust::Distrusted,
+                    (false, true) => Trust::Anchor,
+                    (false, false) => Trust::Unknown,
+                };
+                self.push(cert, origin, trust, object.distrust_after);
+            } else if let Some(key) = object.public_key {
+                match object.extended_key_usage {
+                    Some(purposes) => {
+                        self.purposes
+                            .entry(key)
+                            .or_default()
+                            .extend(purposes);
+                    }
+                    None if object.distrusted => {
+                        self.distrusted_keys.insert(key);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        cert: Vec<u8>,
+        origin: Origin,
+        trust: Trust,
+        distrust_after: Option<SystemTime>,
+    ) {
+        if trust == Trust::Distrusted {
+            self.distrusted_certs
+                .insert(cert.clone());
+        }
+
+        self.certs.push(Entry {
+            cert,
+            origin,
+            trust,
+            distrust_after,
+        });
+    }
+
+    fn apply(self, out: &mut CertificateResult) {
+        for entry in self.certs {
+            let key = x509::subject_public_key_info(&entry.cert).ok();
+            let distrusted = entry.trust == Trust::Distrusted
+                || self
+                    .distrusted_certs
+                    .contains(&entry.cert)
+                || key.is_some_and(|key| self.distrusted_keys.contains(key));
+            let purposes = key.and_then(|key| self.purposes.get(key));
+
+            let reason = match (entry.trust, entry.distrust_after) {
+                _ if distrusted => Some(ExclusionReason::Distrusted),
+                (Trust::Unknown, _) => Some(ExclusionReason::NotTrusted),
+                (_, Some(time)) => Some(ExclusionReason::ServerDistrustAfter { time }),
+                _ if purposes.is_some_and(|purposes| {
+                    !purposes
+                        .iter()
+                        .any(|oid| x509::permits_server_auth(oid))
+                }) =>
+                {
+                    Some(ExclusionReason::NotTrustedForServerAuth)
+                }
+                _ => None,
+            };
+
+            let cert = CertificateDer::from(entry.cert);
+            match reason {
+                Some(reason) => out.exclude(cert, entry.origin, reason),
+                None => out.push_cert(cert, entry.origin),
+            }
+        }
+    }
+}
+
+struct Entry {
+    cert: Vec<u8>,
+    origin: Origin,
+    trust: Trust,
+    distrust_after: Option<SystemTime>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Trust {
+    Anchor,
+    Distrusted,
+    Unknown,
+}
+
+/// The attributes of an object that matter for trust policy.
+#[derive(Default)]
+struct Object {
+    cert: Option<Vec<u8>>,
+    public_key: Option<Vec<u8>>,
+    trusted: bool,
+    distrusted: bool,
+    distrust_after: Option<SystemTime>,
+    extended_key_usage: Option<Vec<Vec<u8>>>,
+}
+
+/// Parse the objects in `text`, yielding the line number of the first error in
+/// each malformed object.
+fn objects(text: &str) -> impl Iterator<Item = Result<Object, usize>> + '_ {
+    let mut lines = text.lines().enumerate().peekable();
+    // Skip anything before the first object.
+    while lines
+        .next_if(|(_, line)| line.trim() != HEADER)
+        .is_some()
+    {}
+
+    std::iter::from_fn(move || {
+        lines.next()?; // header
+
+        let mut attrs = Vec::new();
+        let mut blocks = Vec::new();
+        let mut error = None;
+        while let Some((number, line)) = lines.next_if(|(_, line)| line.trim() != HEADER) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || error.is_some() {
+                continue;
+            }
+
+            if let Some(label) = line
+                .strip_prefix("-----BEGIN ")
+                .and_then(|rest| rest.strip_suffix("-----"))
+            {
+                let end = format!("-----END {label}-----");
+                let mut base64 = String::new();
+                let mut found = false;
+                while let Some((_, line)) = lines.next_if(|(_, line)| line.trim() != HEADER) {
+                    if line.trim() == end {
+                        found = true;
+                        break;
+                    }
+                    base64.push_str(line.trim());
+                }
+
+                match (found, armor::decode_base64(base64.as_bytes())) {
+                    (true, Ok(der)) => blocks.push((label, der)),
+                    _ => error = Some(number + 1),
+                }
+                continue;
+            }
+
+            match line
+                .split_once(':')
+                .and_then(|(name, value)| Some((name.trim(), unquote(value.trim())?)))
+            {
+                Some(attr) => attrs.push((number + 1, attr)),
+                None => error = Some(number + 1),
+            }
+        }
+
+        Some(match error {
+            Some(line) => Err(line),
+            None => object(attrs, blocks),
+        })
+    })
+}
+
+fn object(
+    attrs: Vec<(usize, (&str, Vec<u8>))>,
+    blocks: Vec<(&str, Vec<u8>)>,
+) -> Result<Object, usize> {
+    let mut object = Object::default();
+    let mut class = None;
+    let mut extension = None;
+    for (line, (name, value)) in attrs {
+        match name {
+            "class" => class = Some(value),
+            "trusted" => object.trusted = value == b"true",
+            "x-distrusted" => object.distrusted = value == b"true",
+            "nss-server-distrust-after" if value != b"false" => {
+                object.distrust_after = Some(x509::parse_time(&value).ok_or(line)?);
+            }
+            "value" => extension = Some((line, value)),
+            "public-key-info" => object.public_key = Some(value),
+            _ => {}
+        }
+    }
+
+    for (label, der) in blocks {
+        match label {
+            "CERTIFICATE" => object.cert = Some(der),
+            "PUBLIC KEY" => object.public_key = Some(der),
+            _ => {}
+        }
+    }
+
+    match class.as_deref() {
+        Some(b"certificate") => {
+            if object.cert.is_none() {
+                object.cert = extension.map(|(_, value)| value);
+            }
+        }
+        Some(b"x-certificate-extension") => {
+            if let Some((line, value)) = extension {
+                object.extended_key_usage = extended_key_usage(&value).map_err(|_| line)?;
+            }
+        }
+        Some(b"public-key") => {}
+        _ => {
+            object.cert = None;
+            object.public_key = None;
+        }
+    }
+
+    Ok(object)
+}
+
+/// Parse a DER-encoded `Extension`, returning the purposes if it is an extended
+/// key usage extension.
+fn extended_key_usage(extension: &[u8]) -> Result<Option<Vec<Vec<u8>>>, der::Error> {
+    let mut outer = Reader::new(extension);
+    let mut extension = Reader::new(outer.expect(SEQUENCE)?);
+    if extension.expect(OID)? != EXTENDED_KEY_USAGE {
+        return Ok(None);
+    }
+
+    if extension.remaining().first() == Some(&BOOLEAN) {
+        extension.read()?; // critical
+    }
+
+    let mut value = Reader::new(extension.expect(OCTET_STRING)?);
+    let mut oids = Reader::new(value.expect(SEQUENCE)?);
+    let mut purposes = Vec::new();
+    while !oids.is_empty() {
+        purposes.push(oids.expect(OID)?.to_vec());
+    }
+
+    Ok(Some(purposes))
+}
+
+/// Decode an attribute value: either a bare token, or a quoted string in which
+/// bytes may be percent-encoded.
+fn unquote(value: &str) -> Option<Vec<u8>> {
+    let Some(quoted) = value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return match value.contains(char::is_whitespace) || value.is_empty() {
+            true => None,
+            false => Some(value.as_bytes().to_vec()),
+        };
+    };
+
+    let mut out = Vec::with_capacity(quoted.len());
+    let mut bytes = quoted.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b => out.push(b),
+        }
+    }
+
+    Some(out)
+}
+
+const HEADER: &str = "[p11-kit-object-v1]";
+
+const BOOLEAN: u8 = 0x01;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+
+/// 2.5.29.37
+const EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pki_types::pem::PemObject;
+
+    #[test]
+    fn trust_source_directory() {
+        let result = load_certs_from_p11kit(["tests/p11-kit"]);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let pem = |data: &[u8]| CertificateDer::from_pem_slice(data).unwrap();
+        let mut expected = vec![
+            pem(include_bytes!("../tests/badssl-com-chain.pem")),
+            pem(include_bytes!("../tests/p11-kit/anchors/e.pem")),
+        ];
+        expected.sort_by(|a, b| a.cmp(b));
+        let mut certs = result.certs.clone();
+        certs.sort_by(|a, b| a.cmp(b));
+        assert_eq!(certs, expected);
+
+        let file = Path::new("tests/p11-kit/trust.p11-kit");
+        let reason = |index| {
+            result
+                .excluded
+                .iter()
+                .find(|exclusion| {
+                    exclusion.origin
+                        == Origin::File {
+                            path: file.to_owned(),
+                            index,
+                        }
+                })
+                .map(|exclusion| exclusion.reason.clone())
+        };
+
+        assert_eq!(reason(0), None);
+        assert_eq!(
+            reason(1),
+            Some(ExclusionReason::ServerDistrustAfter {
+                time: x509::parse_time(b"241130235959Z").unwrap()
+            })
+        );
+        // A: stapled EKU for emailProtection only
+        assert_eq!(reason(2), Some(ExclusionReason::NotTrustedForServerAuth));
+        // B: in the blocklist directory
+        assert_eq!(reason(3), Some(ExclusionReason::Distrusted));
+        // C: distrusted public key
+        assert_eq!(reason(4), Some(ExclusionReason::Distrusted));
+        // D: distrusted certificate
+        assert_eq!(reason(5), Some(ExclusionReason::Distrusted));
+        // F: not trusted
+        assert_eq!(reason(6), Some(ExclusionReason::NotTrusted));
+
+        // The blocklisted certificate itself is listed as well.
+        assert_eq!(result.excluded.len(), 7);
+    }
+
+    #[test]
+    fn top_level_certificate_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir
+            .path()
+            .join("trusted-certs.pem");
+        fs::copy("tests/trusted-certs.pem", &path).unwrap();
+
+        let result = load_certs_from_p11kit([temp_dir.path()]);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(
+            result.certs,
+            [
+                CertificateDer::from_pem_slice(include_bytes!("../tests/badssl-com-chain.pem"))
+                    .unwrap()
+            ]
+        );
+        // GlobalSign's trust settings reject serverAuth.
+        assert_eq!(result.excluded.len(), 1);
+        assert_eq!(result.excluded[0].origin, Origin::File { path, index: 1 });
+        assert_eq!(
+            result.excluded[0].reason,
+            ExclusionReason::NotTrustedForServerAuth
+        );
+    }
+
+    #[test]
+    fn distrust_applies_across_paths() {
+        let result = load_certs_from_p11kit(["tests/p11-kit/trust.p11-kit"]);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        // Without the blocklist directory, B is trusted.
+        assert_eq!(result.certs.len(), 2);
+    }
+
+    #[test]
+    fn malformed() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("bad.p11-kit");
+        fs::write(
+            &path,
+            "[p11-kit-object-v1]\nclass: certificate\nlabel \"no colon\"\n\n\
+             [p11-kit-object-v1]\nclass: certificate\nvalue: \"%3\"\n\n\
+             [p11-kit-object-v1]\nclass: certificate\n-----BEGIN CERTIFICATE-----\nMAA=\n\n\
+             [p11-kit-object-v1]\nclass: certificate\ntrusted: true\n\
+             nss-server-distrust-after: \"2411\"\n",
+        )
+        .unwrap();
+
+        let result = load_certs_from_p11kit([&path]);
+        let lines = result
+            .errors
+            .iter()
+            .map(|err| match &err.kind {
+                ErrorKind::MalformedP11Kit { line, .. } => *line,
+                _ => panic!("unexpected error {err:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [3, 7, 11, 17]);
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(unquote("true").unwrap(), b"true");
+        assert_eq!(unquote("\"a%20b c\"").unwrap(), b"a b c");
+        assert_eq!(unquote("\"%5a\"").unwrap(), b"Z");
+        assert!(unquote("\"%5\"").is_none());
+        assert!(unquote("two words").is_none());
+        assert!(unquote("").is_none());
+    }
+}
@@ -0,0 +1,631 @@
+//! Trust anchors from PKCS#12 (`.p12`/`.pfx`) files, as described in
+//! [RFC 7292](https://www.rfc-editor.org/rfc/rfc7292).
+//!
+//! Only certificate bags are read. Key bags are skipped without being decrypted.
+//! Only DER encoding is supported.
+
+use std::path::Path;
+use std::{fmt, fs};
+
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockCipher, BlockDecryptMut, InnerIvInit, KeyInit, KeyIvInit};
+use hmac::{Hmac, Mac};
+use pki_types::CertificateDer;
+use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha384, Sha512};
+
+use crate::der::{self, Reader, SEQUENCE};
+use crate::{CertificateResult, Error, ErrorKind, Origin, Source};
+
+/// Load certificates from the PKCS#12 file at `path`.
+///
+/// This is intended for truststores, which contain only certificates. Private
+/// keys are ignored. `password` is used to verify the file's integrity and to
+/// decrypt the certificates; pass an empty string for files without a password.
+///
+/// Failing to verify or decrypt the file is reported as an
+/// [`ErrorKind::Pkcs12`] error.
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "pkcs12")))]
+pub fn load_certs_from_pkcs12(path: impl AsRef<Path>, password: &str) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    load_pkcs12(path.as_ref(), password, &mut loaded);
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+pub(crate) fn load_pkcs12(path: &Path, password: &str, out: &mut CertificateResult) {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            out.io_error(err, path, "failed to read PKCS#12 file");
+            return;
+        }
+    };
+
+    match certificates(&data, password) {
+        Ok(certs) => {
+            for (index, cert) in certs.into_iter().enumerate() {
+                let origin = Origin::File {
+                    path: path.to_owned(),
+                    index,
+                };
+                out.push_cert(CertificateDer::from(cert), origin);
+            }
+        }
+        Err(inner) => out.errors.push(Error {
+            context: "failed to read PKCS#12 file",
+            kind: ErrorKind::Pkcs12 {
+                inner,
+                path: path.to_owned(),
+            },
+            from: None,
+        }),
+    }
+}
+
+fn certificates(data: &[u8], password: &str) -> Result<Vec<Vec<u8>>, Pkcs12Error> {
+    let mut outer = Reader::new(data);
+    let mut pfx = Reader::new(outer.expect(SEQUENCE)?);
+    if !outer.is_empty() || uint(pfx.expect(INTEGER)?)? != 3 {
+        return Err(Pkcs12Error::Malformed);
+    }
+
+    let auth_safe = data_content(pfx.expect(SEQUENCE)?)?;
+    let candidates = bmp_passwords(password);
+    let bmp_password = match pfx.is_empty() {
+        true => &candidates[0],
+        false => verify_mac(pfx.expect(SEQUENCE)?, auth_safe, &candidates)?,
+    };
+
+    let mut certs = Vec::new();
+    let mut safes = Reader::new(Reader::new(auth_safe).expect(SEQUENCE)?);
+    while !safes.is_empty() {
+        let mut content_info = Reader::new(safes.expect(SEQUENCE)?);
+        let content_type = content_info.expect(OID)?;
+        let mut content = Reader::new(content_info.expect(CONTEXT_0)?);
+        match content_type {
+            DATA => read_bags(content.expect(OCTET_STRING)?, &mut certs)?,
+            ENCRYPTED_DATA => {
+                let mut encrypted_data = Reader::new(content.expect(SEQUENCE)?);
+                encrypted_data.expect(INTEGER)?; // version
+                let plaintext = decrypt(
+                    encrypted_data.expect(SEQUENCE)?,
+                    bmp_password,
+                    password.as_bytes(),
+                )?;
+                read_bags(&plaintext, &mut certs)?;
+            }
+            _ => return Err(Pkcs12Error::UnsupportedAlgorithm),
+        }
+    }
+
+    Ok(certs)
+}
+
+/// Return the contents of a `ContentInfo` of type `data`.
+fn data_content(content_info: &[u8]) -> Result<&[u8], Pkcs12Error> {
+    let mut content_info = Reader::new(content_info);
+    match content_info.expect(OID)? {
+        DATA => Ok(Reader::new(content_info.expect(CONTEXT_0)?).expect(OCTET_STRING)?),
+        _ => Err(Pkcs12Error::UnsupportedAlgorithm),
+    }
+}
+
+/// Verify the `MacData` over `auth_safe`, returning the password that matched.
+fn verify_mac<'a>(
+    mac_data: &[u8],
+    auth_safe: &[u8],
+    candidates: &'a [Vec<u8>],
+) -> Result<&'a [u8], Pkcs12Error> {
+    let mut mac_data = Reader::new(mac_data);
+    let mut digest_info = Reader::new(mac_data.expect(SEQUENCE)?);
+    let mut algorithm = Reader::new(digest_info.expect(SEQUENCE)?);
+    let hash = Hash::from_oid(algorithm.expect(OID)?)?;
+    let expected = digest_info.expect(OCTET_STRING)?;
+    let salt = mac_data.expect(OCTET_STRING)?;
+    let iterations = match mac_data.is_empty() {
+        true => 1,
+        false => iterations(mac_data.expect(INTEGER)?)?,
+    };
+
+    candidates
+        .iter()
+        .find(|password| {
+            let key = kdf(hash, password, salt, MAC_KEY, iterations, hash.output_len());
+            hash.verify_hmac(&key, auth_safe, expected)
+        })
+        .map(|password| password.as_slice())
+        .ok_or(Pkcs12Error::MacMismatch)
+}
+
+/// Decrypt an `EncryptedContentInfo`.
+fn decrypt(
+    encrypted_content_info: &[u8],
+    bmp_password: &[u8],
+    password: &[u8],
+) -> Result<Vec<u8>, Pkcs12Error> {
+    let mut info = Reader::new(encrypted_content_info);
+    info.expect(OID)?; // contentType
+    let mut algorithm = Reader::new(info.expect(SEQUENCE)?);
+    let ciphertext = match info.read()? {
+        (IMPLICIT_0, contents) => contents.to_vec(),
+        (CONTEXT_0, contents) => {
+            let mut chunks = Reader::new(contents);
+            let mut ciphertext = Vec::new();
+            while !chunks.is_empty() {
+                ciphertext.extend_from_slice(chunks.expect(OCTET_STRING)?);
+            }
+            ciphertext
+        }
+        _ => return Err(Pkcs12Error::Malformed),
+    };
+
+    let oid = algorithm.expect(OID)?;
+    if oid == PBES2 {
+        let mut params = Reader::new(algorithm.expect(SEQUENCE)?);
+        return pbes2_decrypt(&mut params, password, ciphertext);
+    }
+
+    let (key_len, effective_bits) = match oid {
+        PBE_SHA1_3DES => (24, 0),
+        PBE_SHA1_RC2_128 => (16, 128),
+        PBE_SHA1_RC2_40 => (5, 40),
+        _ => return Err(Pkcs12Error::UnsupportedAlgorithm),
+    };
+
+    let mut params = Reader::new(algorithm.expect(SEQUENCE)?);
+    let salt = params.expect(OCTET_STRING)?;
+    let iterations = iterations(params.expect(INTEGER)?)?;
+    let key = kdf(Hash::Sha1, bmp_password, salt, KEY, iterations, key_len);
+    let iv = kdf(Hash::Sha1, bmp_password, salt, IV, iterations, 8);
+    match effective_bits {
+        0 => cbc_decrypt::<des::TdesEde3>(&key, &iv, ciphertext),
+        bits => {
+            let cipher = rc2::Rc2::new_with_eff_key_len(&key, bits);
+            let decryptor = cbc::Decryptor::inner_iv_slice_init(cipher, &iv)
+                .map_err(|_| Pkcs12Error::Decryption)?;
+            unpad(decryptor, ciphertext)
+        }
+    }
+}
+
+/// Decrypt using PBES2, as described in RFC 8018 section 6.2.
+fn pbes2_decrypt(
+    params: &mut Reader<'_>,
+    password: &[u8],
+    ciphertext: Vec<u8>,
+) -> Result<Vec<u8>, Pkcs12Error> {
+    let mut kdf = Reader::new(params.expect(SEQUENCE)?);
+    let mut scheme = Reader::new(params.expect(SEQUENCE)?);
+    if kdf.expect(OID)? != PBKDF2 {
+        return Err(Pkcs12Error::UnsupportedAlgorithm);
+    }
+
+    let mut kdf_params = Reader::new(kdf.expect(SEQUENCE)?);
+    let salt = kdf_params.expect(OCTET_STRING)?;
+    let iterations = iterations(kdf_params.expect(INTEGER)?)?;
+    let mut prf = HMAC_WITH_SHA1;
+    while !kdf_params.is_empty() {
+        match kdf_params.read()? {
+            (INTEGER, _) => {} // keyLength, implied by the encryption scheme
+            (SEQUENCE, contents) => prf = Reader::new(contents).expect(OID)?,
+            _ => return Err(Pkcs12Error::Malformed),
+        }
+    }
+
+    let (scheme_oid, iv) = (scheme.expect(OID)?, scheme.expect(OCTET_STRING)?);
+    let key_len = match scheme_oid {
+        AES_128_CBC => 16,
+        AES_192_CBC | DES_EDE3_CBC => 24,
+        AES_256_CBC => 32,
+        _ => return Err(Pkcs12Error::UnsupportedAlgorithm),
+    };
+
+    let mut key = vec![0; key_len];
+    match prf {
+        HMAC_WITH_SHA1 => pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, iterations, &mut key),
+        HMAC_WITH_SHA256 => pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut key),
+        HMAC_WITH_SHA384 => pbkdf2::pbkdf2_hmac::<Sha384>(password, salt, iterations, &mut key),
+        HMAC_WITH_SHA512 => pbkdf2::pbkdf2_hmac::<Sha512>(password, salt, iterations, &mut key),
+        _ => return Err(Pkcs12Error::UnsupportedAlgorithm),
+    }
+
+    match scheme_oid {
+        AES_128_CBC => cbc_decrypt::<aes::Aes128>(&key, iv, ciphertext),
+        AES_192_CBC => cbc_decrypt::<aes::Aes192>(&key, iv, ciphertext),
+        AES_256_CBC => cbc_decrypt::<aes::Aes256>(&key, iv, ciphertext),
+        _ => cbc_decrypt::<des::TdesEde3>(&key, iv, ciphertext),
+    }
+}
+
+fn cbc_decrypt<C: BlockCipher + BlockDecryptMut + KeyInit>(
+    key: &[u8],
+    iv: &[u8],
+    ciphertext: Vec<u8>,
+) -> Result<Vec<u8>, Pkcs12Error> {
+    let decryptor =
+        cbc::Decryptor::<C>::new_from_slices(key, iv).map_err(|_| Pkcs12Error::Decryption)?;
+    unpad(decryptor, ciphertext)
+}
+
+fn unpad<C: BlockCipher + BlockDecryptMut>(
+    decryptor: cbc::Decryptor<C>,
+    mut buf: Vec<u8>,
+) -> Result<Vec<u8>, Pkcs12Error> {
+    let len = decryptor
+        .decrypt_padded_mut::<Pkcs7>(&mut buf)
+        .map_err(|_| Pkcs12Error::Decryption)?
+        .len();
+    buf.truncate(len);
+    Ok(buf)
+}
+
+/// Collect the certificates from the bags in a DER-encoded `SafeContents`.
+fn read_bags(safe_contents: &[u8], certs: &mut Vec<Vec<u8>>) -> Result<(), Pkcs12Error> {
+    let mut bags = Reader::new(Reader::new(safe_contents).expect(SEQUENCE)?);
+    while !bags.is_empty() {
+        let mut bag = Reader::new(bags.expect(SEQUENCE)?);
+        let bag_id = bag.expect(OID)?;
+        let mut value = Reader::new(bag.expect(CONTEXT_0)?);
+        match bag_id {
+            CERT_BAG => {
+                let mut cert_bag = Reader::new(value.expect(SEQUENCE)?);
+                if cert_bag.expect(OID)? != X509_CERTIFICATE {
+                    continue;
+                }
+
+                let mut explicit = Reader::new(cert_bag.expect(CONTEXT_0)?);
+                certs.push(explicit.expect(OCTET_STRING)?.to_vec());
+            }
+            SAFE_CONTENTS_BAG => {
+                let start = value.remaining();
+                value.expect(SEQUENCE)?;
+                read_bags(start, certs)?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Derive key material as described in RFC 7292 appendix B.2.
+fn kdf(hash: Hash, password: &[u8], salt: &[u8], id: u8, iterations: u32, len: usize) -> Vec<u8> {
+    let v = hash.block_len();
+    let fill = |input: &[u8]| -> Vec<u8> {
+        let len = v * ((input.len() + v - 1) / v);
+        input
+            .iter()
+            .cycle()
+            .take(len)
+            .copied()
+            .collect()
+    };
+
+    let diversifier = vec![id; v];
+    let mut i = [fill(salt), fill(password)].concat();
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        let mut a = hash.digest(&[&diversifier, &i]);
+        for _ in 1..iterations {
+            a = hash.digest(&[&a]);
+        }
+
+        let b = a
+            .iter()
+            .cycle()
+            .take(v)
+            .copied()
+            .collect::<Vec<_>>();
+        for block in i.chunks_mut(v) {
+            // block = (block + b + 1) mod 2^(8v)
+            let mut carry = 1;
+            for (x, y) in block.iter_mut().zip(&b).rev() {
+                let sum = u16::from(*x) + u16::from(*y) + carry;
+                *x = sum as u8;
+                carry = sum >> 8;
+            }
+        }
+
+        out.extend_from_slice(&a);
+    }
+
+    out.truncate(len);
+    out
+}
+
+/// Encode `password` as a NUL-terminated BMPString.
+///
+/// An empty password is ambiguous: some implementations encode it as just the
+/// terminator, others as nothing at all. Both are returned in that case.
+fn bmp_passwords(password: &str) -> Vec<Vec<u8>> {
+    let mut bmp = password
+        .encode_utf16()
+        .flat_map(u16::to_be_bytes)
+        .collect::<Vec<_>>();
+    bmp.extend_from_slice(&[0, 0]);
+    match password.is_empty() {
+        true => vec![bmp, Vec::new()],
+        false => vec![bmp],
+    }
+}
+
+/// Parse an iteration count, rejecting counts so large that deriving a key
+/// would take minutes or more.
+fn iterations(contents: &[u8]) -> Result<u32, Pkcs12Error> {
+    match uint(contents)? {
+        iterations @ 1..=MAX_ITERATIONS => Ok(iterations),
+        _ => Err(Pkcs12Error::Malformed),
+    }
+}
+
+fn uint(contents: &[u8]) -> Result<u32, Pkcs12Error> {
+    match contents {
+        [] => Err(Pkcs12Error::Malformed),
+        [first, ..] if first & 0x80 != 0 => Err(Pkcs12Error::Malformed),
+        _ if contents.len() > 5 => Err(Pkcs12Error::Malformed),
+        _ => u32::try_from(
+            contents
+                .iter()
+                .fold(0u64, |acc, b| (acc << 8) | u64::from(*b)),
+        )
+        .map_err(|_| Pkcs12Error::Malformed),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Hash {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Hash {
+    fn from_oid(oid: &[u8]) -> Result<Self, Pkcs12Error> {
+        Ok(match oid {
+            SHA1 => Self::Sha1,
+            SHA256 => Self::Sha256,
+            SHA384 => Self::Sha384,
+            SHA512 => Self::Sha512,
+            _ => return Err(Pkcs12Error::UnsupportedAlgorithm),
+        })
+    }
+
+    fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn digest<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+
+        match self {
+            Self::Sha1 => digest::<Sha1>(parts),
+            Self::Sha256 => digest::<Sha256>(parts),
+            Self::Sha384 => digest::<Sha384>(parts),
+            Self::Sha512 => digest::<Sha512>(parts),
+        }
+    }
+
+    fn verify_hmac(self, key: &[u8], data: &[u8], tag: &[u8]) -> bool {
+        fn verify<M: Mac + KeyInit>(key: &[u8], data: &[u8], tag: &[u8]) -> bool {
+            let Ok(mut mac) = <M as Mac>::new_from_slice(key) else {
+                return false;
+            };
+            mac.update(data);
+            mac.verify_slice(tag).is_ok()
+        }
+
+        match self {
+            Self::Sha1 => verify::<Hmac<Sha1>>(key, data, tag),
+            Self::Sha256 => verify::<Hmac<Sha256>>(key, data, tag),
+            Self::Sha384 => verify::<Hmac<Sha384>>(key, data, tag),
+            Self::Sha512 => verify::<Hmac<Sha512>>(key, data, tag),
+        }
+    }
+
+    fn output_len(self) -> usize {
+        match self {
+            Self::Sha1 => 20,
+            Self::Sha256 => 32,
+            Self::Sha384 => 48,
+            Self::Sha512 => 64,
+        }
+    }
+
+    fn block_len(self) -> usize {
+        match self {
+            Self::Sha1 | Self::Sha256 => 64,
+            Self::Sha384 | Self::Sha512 => 128,
+        }
+    }
+}
+
+/// Why a PKCS#12 file could not be read.
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "pkcs12")))]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pkcs12Error {
+    /// The file is not a valid DER-encoded PKCS#12 file.
+    Malformed,
+    /// The integrity check failed; the password is most likely wrong.
+    MacMismatch,
+    /// Decryption failed; the password is most likely wrong.
+    Decryption,
+    /// The file uses an algorithm or structure that is not supported.
+    UnsupportedAlgorithm,
+}
+
+impl fmt::Display for Pkcs12Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Malformed => "malformed PKCS#12 file",
+            Self::MacMismatch => "MAC verification failed (wrong password?)",
+            Self::Decryption => "decryption failed (wrong password?)",
+            Self::UnsupportedAlgorithm => "unsupported PKCS#12 algorithm or content type",
+        })
+    }
+}
+
+impl std::error::Error for Pkcs12Error {}
+
+impl From<der::Error> for Pkcs12Error {
+    fn from(_: der::Error) -> Self {
+        Self::Malformed
+    }
+}
+
+const OID: u8 = 0x06;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const CONTEXT_0: u8 = 0xa0;
+const IMPLICIT_0: u8 = 0x80;
+
+/// The largest accepted iteration count. Common tools use 2048 to 600,000.
+const MAX_ITERATIONS: u32 = 10_000_000;
+
+// Diversifiers for `kdf()`.
+const KEY: u8 = 1;
+const IV: u8 = 2;
+const MAC_KEY: u8 = 3;
+
+/// 1.2.840.113549.1.7.1
+const DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+/// 1.2.840.113549.1.7.6
+const ENCRYPTED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x06];
+/// 1.2.840.113549.1.12.10.1.3
+const CERT_BAG: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x03,
+];
+/// 1.2.840.113549.1.12.10.1.6
+const SAFE_CONTENTS_BAG: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x06,
+];
+/// 1.2.840.113549.1.9.22.1
+const X509_CERTIFICATE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x16, 0x01];
+
+/// 1.2.840.113549.1.12.1.3
+const PBE_SHA1_3DES: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x01, 0x03];
+/// 1.2.840.113549.1.12.1.5
+const PBE_SHA1_RC2_128: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x01, 0x05];
+/// 1.2.840.113549.1.12.1.6
+const PBE_SHA1_RC2_40: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x01, 0x06];
+/// 1.2.840.113549.1.5.13
+const PBES2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+/// 1.2.840.113549.1.5.12
+const PBKDF2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+
+/// 1.2.840.113549.2.7
+const HMAC_WITH_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x07];
+/// 1.2.840.113549.2.9
+const HMAC_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x09];
+/// 1.2.840.113549.2.10
+const HMAC_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x0a];
+/// 1.2.840.113549.2.11
+const HMAC_WITH_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x0b];
+
+/// 2.16.840.1.101.3.4.1.2
+const AES_128_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x02];
+/// 2.16.840.1.101.3.4.1.22
+const AES_192_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x16];
+/// 2.16.840.1.101.3.4.1.42
+const AES_256_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+/// 1.2.840.113549.3.7
+const DES_EDE3_CBC: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x03, 0x07];
+
+/// 1.3.14.3.2.26
+const SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+/// 2.16.840.1.101.3.4.2.1
+const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+/// 2.16.840.1.101.3.4.2.2
+const SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+/// 2.16.840.1.101.3.4.2.3
+const SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truststores() {
+        for (path, password, expected) in [
+            // OpenSSL 3 defaults: PBES2 with AES-256-CBC, HMAC-SHA256
+            ("tests/truststore.p12", "changeit", 2),
+            // `-certpbe PBE-SHA1-3DES -macalg sha1`
+            ("tests/truststore-legacy.p12", "changeit", 2),
+            // `-legacy`: 40-bit RC2
+            ("tests/truststore-rc2.p12", "changeit", 2),
+            // `-certpbe NONE` with an empty password
+            ("tests/truststore-nopass.p12", "", 2),
+            // `keytool -importcert -storetype PKCS12`
+            ("tests/truststore-java.p12", "changeit", 1),
+        ] {
+            let result = load_certs_from_pkcs12(path, password);
+            assert!(result.errors.is_empty(), "{path}: {:?}", result.errors);
+            assert_eq!(result.certs.len(), expected, "{path}");
+            assert_eq!(
+                result.provenance().count(),
+                expected,
+                "{path}: missing provenance"
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_password() {
+        for path in ["tests/truststore.p12", "tests/truststore-legacy.p12"] {
+            let result = load_certs_from_pkcs12(path, "wrong");
+            assert!(result.certs.is_empty());
+            match &result.errors[0].kind {
+                ErrorKind::Pkcs12 { inner, .. } => assert_eq!(*inner, Pkcs12Error::MacMismatch),
+                _ => panic!("unexpected error {:?}", result.errors),
+            }
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let data = include_bytes!("../tests/truststore.p12");
+        assert_eq!(
+            certificates(&data[..data.len() - 1], "changeit"),
+            Err(Pkcs12Error::Malformed)
+        );
+        assert_eq!(
+            certificates(include_bytes!("../tests/bundle-der.p7b"), "changeit"),
+            Err(Pkcs12Error::Malformed)
+        );
+    }
+
+    #[test]
+    fn iteration_limit() {
+        assert_eq!(iterations(&[0x08, 0x00]), Ok(2048));
+        assert_eq!(iterations(&[0x00, 0x98, 0x96, 0x80]), Ok(MAX_ITERATIONS));
+        assert_eq!(
+            iterations(&[0x00, 0x98, 0x96, 0x81]),
+            Err(Pkcs12Error::Malformed)
+        );
+        assert_eq!(
+            iterations(&[0x00, 0xff, 0xff, 0xff, 0xff]),
+            Err(Pkcs12Error::Malformed)
+        );
+        assert_eq!(iterations(&[0x00]), Err(Pkcs12Error::Malformed));
+    }
+
+    #[test]
+    fn kdf_vectors() {
+        // From https://cryptopp.com/wiki/PKCS12_PBKDF and cross-checked against OpenSSL.
+        let password = bmp_passwords("smeg").remove(0);
+        let salt = [0x0a, 0x58, 0xcf, 0x64, 0x53, 0x0d, 0x82, 0x3f];
+        assert_eq!(
+            kdf(Hash::Sha1, &password, &salt, KEY, 1, 24),
+            [
+                0x8a, 0xaa, 0xe6, 0x29, 0x7b, 0x6c, 0xb0, 0x46, 0x42, 0xab, 0x5b, 0x07, 0x78, 0x51,
+                0x28, 0x4e, 0xb7, 0x12, 0x8f, 0x1a, 0x2a, 0x7f, 0xbc, 0xa3
+            ]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Certificates from PKCS#7 (CMS) `SignedData` bundles, as described in
+//! [RFC 2315](https://www.rfc-editor.org/rfc/rfc2315) and
+//! [RFC 5652](https://www.rfc-editor.org/rfc/rfc5652).
+//!
+//! Only the `certificates` field is used. These bundles are usually
+//! "certs-only", without any content or signers.
+
+use crate::der::{self, Reader, SEQUENCE};
+
+/// Whether `data` looks like a DER-encoded PKCS#7 `ContentInfo`.
+///
+/// Only the start of `data` is inspected, so that truncated bundles are
+/// recognized as such.
+pub(crate) fn is_pkcs7(data: &[u8]) -> bool {
+    let rest = match data.split_first() {
+        Some((&SEQUENCE, rest)) => rest,
+        _ => return false,
+    };
+
+    let len_bytes = match rest.first() {
+        Some(&b @ 0x81..=0x84) => usize::from(b & 0x7f) + 1,
+        Some(_) => 1,
+        None => return false,
+    };
+
+    let contents = rest
+        .get(len_bytes..)
+        .unwrap_or_default();
+    match Reader::new(contents).expect(OID) {
+        Ok(oid) => oid.starts_with(PKCS7),
+        Err(_) => false,
+    }
+}
+
+/// Extract the DER-encoded certificates from a PKCS#7 `SignedData` bundle.
+pub(crate) fn certificates(data: &[u8]) -> Result<Vec<&[u8]>, der::Error> {
+    let mut outer = Reader::new(data);
+    let mut content_info = Reader::new(outer.expect(SEQUENCE)?);
+    if !outer.is_empty() || content_info.expect(OID)? != SIGNED_DATA {
+        return Err(der::Error);
+    }
+
+    let mut explicit = Reader::new(content_info.expect(CONTEXT_0)?);
+    let mut signed_data = Reader::new(explicit.expect(SEQUENCE)?);
+    signed_data.expect(INTEGER)?; // version
+    signed_data.expect(SET)?; // digestAlgorithms
+    signed_data.expect(SEQUENCE)?; // encapContentInfo
+
+    let mut certs = Vec::new();
+    let (tag, contents) = match signed_data.is_empty() {
+        true => return Err(der::Error),
+        false => signed_data.read()?,
+    };
+
+    if tag != CONTEXT_0 {
+        return Ok(certs);
+    }
+
+    let mut set = Reader::new(contents);
+    while !set.is_empty() {
+        let start = set.remaining();
+        let (tag, _) = set.read()?;
+        // Skip the obsolete certificate choices; only keep X.509 certificates.
+        if tag == SEQUENCE {
+            certs.push(&start[..start.len() - set.remaining().len()]);
+        }
+    }
+
+    Ok(certs)
+}
+
+const OID: u8 = 0x06;
+const INTEGER: u8 = 0x02;
+const SET: u8 = 0x31;
+const CONTEXT_0: u8 = 0xa0;
+
+/// 1.2.840.113549.1.7
+const PKCS7: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07];
+/// 1.2.840.113549.1.7.2
+const SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certs_only_bundle() {
+        let data = include_bytes!("../tests/bundle-der.p7b");
+        assert!(is_pkcs7(data));
+        let certs = certificates(data).unwrap();
+        assert_eq!(certs.len(), 2);
+        assert!(der::split_certificates(certs[0]).is_some());
+    }
+
+    #[test]
+    fn malformed() {
+        let data = include_bytes!("../tests/bundle-der.p7b");
+        assert!(certificates(&data[..data.len() - 1]).is_err());
+        assert!(!is_pkcs7(include_bytes!("../tests/bundle-pem.p7b")));
+    }
+}
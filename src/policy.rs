@@ -0,0 +1,220 @@
+//! Policies that select which loaded certificates are used as trust anchors,
+//! applied the same way to every source.
+
+use pki_types::CertificateDer;
+
+use crate::der::Reader;
+use crate::info::{dotted, BasicConstraints};
+use crate::{x509, ExclusionReason};
+
+/// A purpose a certificate may be used for, as listed in its extended key usage
+/// extension.
+///
+/// See [`Loader::purposes()`][crate::Loader::purposes].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KeyPurpose {
+    /// TLS server authentication, `id-kp-serverAuth` (1.3.6.1.5.5.7.3.1).
+    ServerAuth,
+    /// TLS client authentication, `id-kp-clientAuth` (1.3.6.1.5.5.7.3.2).
+    ClientAuth,
+    /// Any other purpose, by its object identifier in dotted form, such as
+    /// `1.3.6.1.5.5.7.3.3` for code signing.
+    Other(String),
+}
+
+impl KeyPurpose {
+    /// The object identifier of the purpose, in dotted form.
+    pub fn oid(&self) -> &str {
+        match self {
+            Self::ServerAuth => "1.3.6.1.5.5.7.3.1",
+            Self::ClientAuth => "1.3.6.1.5.5.7.3.2",
+            Self::Other(oid) => oid,
+        }
+    }
+}
+
+/// Why `cert` should be excluded given the required `purposes`, if it should.
+///
+/// A certificate is suitable if it has no extended key usage extension, or if the
+/// extension lists `anyExtendedKeyUsage` or any one of `purposes`. Certificates
+/// that can't be parsed are kept.
+pub(crate) fn check_purposes(
+    cert: &CertificateDer<'_>,
+    purposes: &[KeyPurpose],
+) -> Option<ExclusionReason> {
+    let allowed = x509::extended_key_usage(cert).ok()??;
+    let allowed = allowed
+        .iter()
+        .map(|oid| dotted(oid).unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    let suitable = allowed.iter().any(|oid| {
+        oid == ANY_EXTENDED_KEY_USAGE
+            || purposes
+                .iter()
+                .any(|purpose| purpose.oid() == oid)
+    });
+    match suitable {
+        true => None,
+        false => Some(ExclusionReason::NotValidForPurpose { allowed }),
+    }
+}
+
+/// How certificates that are not CA certificates are treated.
+///
+/// A CA certificate has a basic constraints extension with `cA` set, and either no
+/// key usage extension or one that allows `keyCertSign`. Certificates without
+/// extensions, such as X.509 version 1 roots, are not CA certificates by this
+/// definition, and neither are certificates that can't be parsed.
+///
+/// See [`Loader::ca_policy()`][crate::Loader::ca_policy].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaPolicy {
+    /// Don't check.
+    #[default]
+    Ignore,
+    /// Keep certificates that are not CA certificates, but report each one in
+    /// [`CertificateResult::warnings`][crate::CertificateResult::warnings] as an
+    /// [`ErrorKind::NotCa`][crate::ErrorKind::NotCa].
+    Warn,
+    /// Exclude certificates that are not CA certificates, listing them in
+    /// [`CertificateResult::excluded`][crate::CertificateResult::excluded] as
+    /// [`ExclusionReason::NotCa`].
+    Exclude,
+}
+
+/// Whether `cert` is a CA certificate, as defined for [`CaPolicy`].
+pub(crate) fn is_ca(cert: &CertificateDer<'_>) -> bool {
+    let Ok(extensions) = x509::extensions(cert) else {
+        return false;
+    };
+
+    let mut ca = false;
+    for extension in extensions {
+        match extension.oid {
+            BASIC_CONSTRAINTS => {
+                ca = BasicConstraints::parse(extension.value).is_ok_and(|bc| bc.ca);
+            }
+            KEY_USAGE if !allows_key_cert_sign(extension.value) => return false,
+            _ => {}
+        }
+    }
+    ca
+}
+
+/// Whether the key usage extension value `value` allows `keyCertSign`.
+fn allows_key_cert_sign(value: &[u8]) -> bool {
+    match Reader::new(value).expect(BIT_STRING) {
+        // The first byte counts the unused bits; `keyCertSign` is bit 5.
+        Ok([_, first, ..]) => first & 0x04 != 0,
+        _ => false,
+    }
+}
+
+const ANY_EXTENDED_KEY_USAGE: &str = "2.5.29.37.0";
+const BIT_STRING: u8 = 0x03;
+
+/// 2.5.29.15
+const KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+/// 2.5.29.19
+const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::der::{self, SEQUENCE};
+
+    /// A minimal certificate with the given extended key usage OIDs, if any.
+    fn cert(ekus: Option<&[&[u8]]>) -> CertificateDer<'static> {
+        let mut tbs = Vec::new();
+        der::write(0x02, &[0x01], &mut tbs); // serialNumber
+        for _ in 0..4 {
+            der::write(SEQUENCE, &[], &mut tbs); // signature, issuer, validity, subject
+        }
+        der::write(SEQUENCE, &[], &mut tbs); // subjectPublicKeyInfo
+        if let Some(ekus) = ekus {
+            let mut oids = Vec::new();
+            for oid in ekus {
+                der::write(0x06, oid, &mut oids);
+            }
+            let mut value = Vec::new();
+            der::write(SEQUENCE, &oids, &mut value);
+            let mut extension = Vec::new();
+            der::write(0x06, &[0x55, 0x1d, 0x25], &mut extension);
+            der::write(0x04, &value, &mut extension);
+            let mut extensions = Vec::new();
+            der::write(SEQUENCE, &extension, &mut extensions);
+            let mut sequence = Vec::new();
+            der::write(SEQUENCE, &extensions, &mut sequence);
+            der::write(0xa3, &sequence, &mut tbs);
+        }
+
+        let mut certificate = Vec::new();
+        der::write(SEQUENCE, &tbs, &mut certificate);
+        let mut out = Vec::new();
+        der::write(SEQUENCE, &certificate, &mut out);
+        CertificateDer::from(out)
+    }
+
+    const SERVER_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+    const CODE_SIGNING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
+    const EMAIL_PROTECTION: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x04];
+    const ANY: &[u8] = &[0x55, 0x1d, 0x25, 0x00];
+
+    #[test]
+    fn ca_certificates() {
+        use pki_types::pem::PemObject;
+
+        let pem = |pem: &[u8]| CertificateDer::from_pem_slice(pem).unwrap();
+        // basicConstraints cA, keyUsage keyCertSign and cRLSign
+        assert!(is_ca(&pem(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))));
+        assert!(is_ca(&pem(include_bytes!("../tests/code-signing-ca.pem"))));
+        // No extensions
+        assert!(!is_ca(&cert(None)));
+        assert!(!is_ca(&pem(include_bytes!(
+            "../tests/badssl-com-chain.pem"
+        ))));
+        assert!(!is_ca(&CertificateDer::from(vec![0x30, 0x00])));
+
+        // digitalSignature only
+        assert!(!allows_key_cert_sign(&[0x03, 0x02, 0x07, 0x80]));
+        // keyCertSign, cRLSign
+        assert!(allows_key_cert_sign(&[0x03, 0x02, 0x01, 0x06]));
+        assert!(!allows_key_cert_sign(&[0x03, 0x01, 0x00]));
+    }
+
+    #[test]
+    fn purposes() {
+        let server = [KeyPurpose::ServerAuth];
+        assert_eq!(check_purposes(&cert(None), &server), None);
+        assert_eq!(check_purposes(&cert(Some(&[SERVER_AUTH])), &server), None);
+        assert_eq!(check_purposes(&cert(Some(&[ANY])), &server), None);
+        assert_eq!(
+            check_purposes(&cert(Some(&[CODE_SIGNING, EMAIL_PROTECTION])), &server),
+            Some(ExclusionReason::NotValidForPurpose {
+                allowed: vec!["1.3.6.1.5.5.7.3.3".into(), "1.3.6.1.5.5.7.3.4".into()]
+            })
+        );
+
+        let code_signing = [
+            KeyPurpose::ClientAuth,
+            KeyPurpose::Other("1.3.6.1.5.5.7.3.3".into()),
+        ];
+        assert_eq!(
+            check_purposes(&cert(Some(&[CODE_SIGNING])), &code_signing),
+            None
+        );
+        assert!(check_purposes(&cert(Some(&[SERVER_AUTH])), &code_signing).is_some());
+
+        // Unparsable certificates are kept.
+        assert_eq!(
+            check_purposes(&CertificateDer::from(vec![0x30, 0x00]), &server),
+            None
+        );
+    }
+}
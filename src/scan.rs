@@ -0,0 +1,871 @@
+//! Scanning certificate directories, optionally recursively and with patterns
+//! selecting which files to load.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use pki_types::pem;
+
+use crate::{load_file_data, CertificateResult, Error, ErrorKind, Source};
+
+/// Load certificates from the files in the directory `dir`, scanned according to
+/// `options`.
+///
+/// Each file follows the same rules as the `file` argument of
+/// [`load_certs_from_paths()`][crate::load_certs_from_paths]. Files and directories
+/// that are passed over are listed in [`CertificateResult::skipped`].
+///
+/// ```no_run
+/// use rustls_native_certs::{load_certs_from_dir, DirOptions};
+///
+/// // Load `vendor/<name>/*.pem`, but not `vendor/old/*.pem`.
+/// let options = DirOptions::new()
+///     .max_depth(1)
+///     .extension("pem")
+///     .exclude("old/**");
+/// let result = load_certs_from_dir("vendor", &options);
+/// ```
+pub fn load_certs_from_dir(dir: impl AsRef<Path>, options: &DirOptions) -> CertificateResult {
+    let mut loaded = CertificateResult::default();
+    scan_dir(dir.as_ref(), options, &mut loaded);
+    loaded.dedup();
+
+    let mut out = CertificateResult::default();
+    out.extend(loaded, Some(Source::Configured));
+    out
+}
+
+/// How to scan a certificate directory.
+///
+/// By default, only the files directly in the directory are loaded, symlinks to
+/// files are followed, and symlinks to directories are not. This is how
+/// [`load_certs_from_paths()`][crate::load_certs_from_paths] treats directories.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirOptions {
+    max_depth: usize,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    symlinks: SymlinkPolicy,
+    containment: SymlinkContainment,
+}
+
+impl DirOptions {
+    /// Options for a non-recursive scan that loads every file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Descend into at most `depth` levels of subdirectories.
+    ///
+    /// With 0, the default, only the files directly in the directory are loaded;
+    /// with 1, files in its subdirectories are loaded too, and so on.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Load only files matching `pattern`, or one of the other included patterns.
+    ///
+    /// Patterns are globs: `*` matches any characters other than `/`, `**` matches
+    /// any characters including `/`, and `?` matches any single character other than
+    /// `/`. A pattern containing `/` is matched against the path of a file relative
+    /// to the scanned directory, with `/` separating its components; any other
+    /// pattern is matched against the file name. May be called more than once.
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Load only files with the extension `extension`, such as `"pem"`.
+    ///
+    /// Shorthand for `include("*.<extension>")`.
+    pub fn extension(self, extension: &str) -> Self {
+        self.include(format!("*.{extension}"))
+    }
+
+    /// Skip files and directories matching `pattern`.
+    ///
+    /// Patterns are matched as for [`DirOptions::include()`], and take precedence
+    /// over them. A directory that matches is not scanned at all. A pattern ending
+    /// in `/**`, such as `old/**`, matches the directory `old` as well as
+    /// everything in it. May be called more than once.
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Which symlinks to follow. Defaults to [`SymlinkPolicy::Files`].
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Where followed symlinks may point. Defaults to [`SymlinkContainment::Anywhere`].
+    ///
+    /// A symlink that would be followed, but points elsewhere, is not followed and
+    /// gives an [`ErrorKind::SymlinkNotAllowed`] error.
+    pub fn symlink_containment(mut self, containment: SymlinkContainment) -> Self {
+        self.containment = containment;
+        self
+    }
+
+    /// Check that the symlink at `path` may be followed, returning the path to read
+    /// in its place if so, and its target if not.
+    ///
+    /// A contained symlink is replaced by its canonical target, so that changing
+    /// the symlink after the check cannot redirect the read.
+    fn check_symlink(&self, path: &Path) -> Result<PathBuf, PathBuf> {
+        match &self.containment {
+            SymlinkContainment::Anywhere => Ok(path.to_owned()),
+            SymlinkContainment::Within(roots) => {
+                let target = fs::canonicalize(path).map_err(|_| path.to_owned())?;
+                match roots.iter().any(|root| {
+                    target.starts_with(fs::canonicalize(root).unwrap_or_else(|_| root.clone()))
+                }) {
+                    true => Ok(target),
+                    false => Err(target),
+                }
+            }
+            SymlinkContainment::Deny => Err(fs::read_link(path).unwrap_or_default()),
+        }
+    }
+
+    /// Read the file at `resolved`, which was checked to have `metadata`.
+    ///
+    /// Unless symlinks may point anywhere, the file that is opened must be the one
+    /// that was checked, so that replacing it with a symlink after the check
+    /// cannot redirect the read.
+    fn read_checked(&self, resolved: &Path, metadata: &fs::Metadata) -> io::Result<Vec<u8>> {
+        let mut file = File::open(resolved)?;
+        if self.containment != SymlinkContainment::Anywhere
+            && !same_file(&file.metadata()?, metadata)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "file was replaced after it was checked",
+            ));
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Whether the entry at `relative` is skipped by the patterns.
+    fn skip_reason(&self, relative: &str, is_dir: bool) -> Option<SkipReason> {
+        // A directory is matched with a trailing `/` as well, so that `old/**`
+        // skips `old` itself rather than each file in it.
+        let dir = format!("{relative}/");
+        if self.exclude.iter().any(|pattern| {
+            matches(pattern, relative)
+                || (is_dir && pattern.contains('/') && matches(pattern, &dir))
+        }) {
+            return Some(SkipReason::Excluded);
+        }
+
+        match is_dir
+            || self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| matches(pattern, relative))
+        {
+            true => None,
+            false => Some(SkipReason::NotIncluded),
+        }
+    }
+}
+
+/// Which symlinks are followed when scanning a directory.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Follow symlinks to files, but not to directories.
+    ///
+    /// Hashed directories created by `openssl rehash` consist of such symlinks.
+    #[default]
+    Files,
+    /// Follow symlinks to files and directories.
+    ///
+    /// Each directory is scanned at most once, so symlink loops are harmless.
+    All,
+    /// Follow no symlinks.
+    None,
+}
+
+/// Where symlinks followed when scanning a directory may point.
+///
+/// A certificate directory that others can write to may otherwise be used to make
+/// the scan read any file, such as files in `/proc`.
+///
+/// Other than with [`SymlinkContainment::Anywhere`], a file that is replaced after
+/// it was checked, for example by a symlink, is not read, and gives an
+/// [`ErrorKind::Io`] error.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkContainment {
+    /// Symlinks may point anywhere.
+    #[default]
+    Anywhere,
+    /// Symlinks must resolve to a path inside one of these directories, such as
+    /// `/usr/share/ca-certificates`.
+    Within(Vec<PathBuf>),
+    /// No symlinks may be followed.
+    Deny,
+}
+
+/// A file or directory that was passed over while scanning a directory.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Skipped {
+    /// The path of the file or directory.
+    pub path: PathBuf,
+    /// Why it was skipped.
+    pub reason: SkipReason,
+}
+
+/// Why a file or directory was skipped.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file matched none of the patterns given to [`DirOptions::include()`].
+    NotIncluded,
+    /// The file or directory matched a pattern given to [`DirOptions::exclude()`].
+    Excluded,
+    /// The directory is deeper than [`DirOptions::max_depth()`] allows.
+    TooDeep,
+    /// The symlink is not followed, according to [`DirOptions::symlinks()`].
+    Symlink,
+    /// The symlink's target does not exist.
+    DanglingSymlink,
+    /// The directory was already scanned, through a symlink.
+    AlreadyScanned,
+    /// The entry is neither a file nor a directory; for example, a socket.
+    NotRegular,
+}
+
+/// Load certificates from `dir` into `out`, according to `options`.
+pub(crate) fn scan_dir(dir: &Path, options: &DirOptions, out: &mut CertificateResult) {
+    let mut scanned = HashSet::new();
+    if let Ok(canonical) = fs::canonicalize(dir) {
+        scanned.insert(canonical);
+    }
+
+    let mut pending = vec![(dir.to_owned(), String::new(), 0)];
+    while let Some((dir, relative, depth)) = pending.pop() {
+        let dir_reader = match fs::read_dir(&dir) {
+            Ok(reader) => reader,
+            Err(err) => {
+                out.io_error(err, &dir, "opening directory");
+                continue;
+            }
+        };
+
+        for entry in dir_reader {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    out.io_error(err, &dir, "reading directory entries");
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            let relative = format!("{relative}{}", entry.file_name().to_string_lossy());
+            let mut skip = |reason| {
+                out.skipped.push(Skipped {
+                    path: path.clone(),
+                    reason,
+                })
+            };
+
+            let is_symlink = entry
+                .file_type()
+                .is_ok_and(|file_type| file_type.is_symlink());
+            if is_symlink && options.symlinks == SymlinkPolicy::None {
+                skip(SkipReason::Symlink);
+                continue;
+            }
+
+            // `openssl rehash` used to create this directory uses symlinks. So,
+            // make sure we resolve them.
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    skip(SkipReason::DanglingSymlink);
+                    continue;
+                }
+                Err(e) => {
+                    out.io_error(e, &path, "failed to open file");
+                    continue;
+                }
+            };
+
+            let contained = || match is_symlink {
+                true => options.check_symlink(&path),
+                false => Ok(path.clone()),
+            };
+            let not_allowed = |target| Error {
+                context: "refusing to follow symlink",
+                kind: ErrorKind::SymlinkNotAllowed {
+                    path: path.clone(),
+                    target,
+                },
+                from: None,
+            };
+
+            if metadata.is_file() {
+                if let Some(reason) = options.skip_reason(&relative, false) {
+                    skip(reason);
+                } else {
+                    match contained() {
+                        Ok(resolved) => {
+                            #[cfg(test)]
+                            tests::BEFORE_READ.with(|hook| {
+                                if let Some(hook) = &*hook.borrow() {
+                                    hook(&path);
+                                }
+                            });
+                            match options.read_checked(&resolved, &metadata) {
+                                Ok(data) => load_file_data(data, &path, out),
+                                Err(err) => out.pem_error(pem::Error::Io(err), &path),
+                            }
+                        }
+                        Err(target) => out.errors.push(not_allowed(target)),
+                    }
+                }
+                continue;
+            } else if !metadata.is_dir() {
+                skip(SkipReason::NotRegular);
+                continue;
+            }
+
+            if let Some(reason) = options.skip_reason(&relative, true) {
+                skip(reason);
+            } else if depth >= options.max_depth {
+                skip(SkipReason::TooDeep);
+            } else if is_symlink && options.symlinks != SymlinkPolicy::All {
+                skip(SkipReason::Symlink);
+            } else {
+                match contained() {
+                    Ok(resolved) => match fs::canonicalize(&resolved)
+                        .is_ok_and(|canonical| !scanned.insert(canonical))
+                    {
+                        true => skip(SkipReason::AlreadyScanned),
+                        false => pending.push((resolved, format!("{relative}/"), depth + 1)),
+                    },
+                    Err(target) => out.errors.push(not_allowed(target)),
+                }
+            }
+        }
+    }
+}
+
+/// Whether `a` and `b` describe the same file.
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+/// Whether `a` and `b` describe the same file, as far as the portable metadata can
+/// tell.
+#[cfg(not(unix))]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    a.len() == b.len()
+        && a.modified().ok() == b.modified().ok()
+        && a.created().ok() == b.created().ok()
+}
+
+/// Whether the glob `pattern` matches `relative`, a path relative to the scanned
+/// directory; see [`DirOptions::include()`].
+fn matches(pattern: &str, relative: &str) -> bool {
+    let text = match pattern.contains('/') {
+        true => relative,
+        false => relative
+            .rsplit('/')
+            .next()
+            .unwrap_or(relative),
+    };
+
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    glob(&pattern, &text)
+}
+
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the most recent `*` and `**`: the pattern position just
+    // past it, and how much of the text it has consumed. On a mismatch, only the
+    // most recent star consumes more, as `**` can absorb anything an earlier star
+    // could, and `*` cannot cross the `/` that ends its component.
+    let mut star = None;
+    let mut double_star = None;
+    while p < pattern.len() || t < text.len() {
+        match pattern.get(p) {
+            Some('*') if pattern.get(p + 1) == Some(&'*') => {
+                // `**/` also matches no directories at all, so it consumes either
+                // nothing or text up to and including a `/`.
+                let dirs = pattern.get(p + 2) == Some(&'/');
+                p += if dirs { 3 } else { 2 };
+                double_star = Some((p, t, dirs));
+                star = None;
+                continue;
+            }
+            Some('*') => {
+                p += 1;
+                star = Some((p, t));
+                continue;
+            }
+            Some('?') if text.get(t).is_some_and(|&c| c != '/') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(c) if *c != '?' && text.get(t) == Some(c) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some((star_p, star_t)) = star {
+            if text
+                .get(star_t)
+                .is_some_and(|&c| c != '/')
+            {
+                star = Some((star_p, star_t + 1));
+                (p, t) = (star_p, star_t + 1);
+                continue;
+            }
+        }
+
+        let Some((star_p, star_t, dirs)) = double_star else {
+            return false;
+        };
+        let next = match dirs {
+            true => text[star_t..]
+                .iter()
+                .position(|&c| c == '/')
+                .map(|i| star_t + i + 1),
+            false => (star_t < text.len()).then_some(star_t + 1),
+        };
+        let Some(next) = next else {
+            return false;
+        };
+        double_star = Some((star_p, next, dirs));
+        star = None;
+        (p, t) = (star_p, next);
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::slice;
+
+    use pki_types::pem::PemObject;
+    use pki_types::CertificateDer;
+
+    use crate::Origin;
+
+    type Hook = Box<dyn Fn(&Path)>;
+
+    thread_local! {
+        /// Called with the path of each file after it is checked, just before it is read.
+        pub(super) static BEFORE_READ: RefCell<Option<Hook>> = RefCell::new(None);
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(matches("*.pem", "a.pem"));
+        assert!(matches("*.pem", "vendor/a/b.pem"));
+        assert!(!matches("*.pem", "a.pem.bak"));
+        assert!(matches("?.pem", "a.pem"));
+        assert!(!matches("?.pem", "ab.pem"));
+        assert!(matches("vendor/*/*.pem", "vendor/a/b.pem"));
+        assert!(!matches("vendor/*.pem", "vendor/a/b.pem"));
+        assert!(matches("vendor/**/*.pem", "vendor/a/b/c.pem"));
+        assert!(matches("vendor/**/*.pem", "vendor/c.pem"));
+        assert!(matches("**", "a/b"));
+        assert!(matches("README*", "vendor/README.md"));
+        assert!(!matches("a/b", "a/b/c"));
+        assert!(matches("old/**", "old/"));
+        assert!(matches("a/**/b/*.pem", "a/x/b/y/b/c.pem"));
+        assert!(!matches("a/**/b/*.pem", "a/x/b/y/c.pem"));
+        assert!(matches("*a*b", "xaxab"));
+        assert!(!matches("*a*b", "xa/b"));
+
+        // Would take exponential time with backtracking at every star.
+        let long = format!("{}b", "a/".repeat(200));
+        assert!(!matches(&format!("{}c", "**a".repeat(50)), &long));
+        assert!(matches(&format!("{}b", "**a/".repeat(50)), &long));
+    }
+
+    #[test]
+    fn recursive() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let chain = include_bytes!("../tests/badssl-com-chain.pem");
+        let globalsign = include_bytes!("../integration-tests/one-existing-ca.pem");
+        let same_subject = include_bytes!("../tests/same-subject.pem");
+
+        fs::create_dir_all(root.join("vendor/a/deep")).unwrap();
+        fs::create_dir_all(root.join("vendor/old")).unwrap();
+        fs::write(root.join("top.pem"), chain).unwrap();
+        fs::write(root.join("vendor/README"), "not a certificate").unwrap();
+        fs::write(root.join("vendor/a/a.pem"), globalsign).unwrap();
+        fs::write(root.join("vendor/a/a.pem.bak"), [0x01]).unwrap();
+        fs::write(root.join("vendor/a/deep/deep.pem"), same_subject).unwrap();
+        fs::write(root.join("vendor/old/old.pem"), same_subject).unwrap();
+
+        let count = |pem: &[u8]| CertificateDer::pem_slice_iter(pem).count();
+        let skipped = |result: &CertificateResult| {
+            let mut skipped = result
+                .skipped
+                .iter()
+                .map(|s| {
+                    (
+                        s.path
+                            .strip_prefix(root)
+                            .unwrap()
+                            .to_str()
+                            .unwrap()
+                            .to_owned(),
+                        s.reason,
+                    )
+                })
+                .collect::<Vec<_>>();
+            skipped.sort_by(|a, b| a.0.cmp(&b.0));
+            skipped
+        };
+
+        // Not recursive by default.
+        let result = load_certs_from_dir(root, &DirOptions::new());
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), count(chain));
+        assert_eq!(
+            skipped(&result),
+            [("vendor".to_owned(), SkipReason::TooDeep)]
+        );
+
+        let options = DirOptions::new()
+            .max_depth(2)
+            .extension("pem")
+            .exclude("vendor/old");
+        let result = load_certs_from_dir(root, &options);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.certs.len(), count(chain) + count(globalsign));
+        assert_eq!(
+            skipped(&result),
+            [
+                ("vendor/README".to_owned(), SkipReason::NotIncluded),
+                ("vendor/a/a.pem.bak".to_owned(), SkipReason::NotIncluded),
+                ("vendor/a/deep".to_owned(), SkipReason::TooDeep),
+                ("vendor/old".to_owned(), SkipReason::Excluded),
+            ]
+        );
+        assert!(result
+            .provenance()
+            .all(|(_, p)| p.shadowed.is_empty()));
+        assert_eq!(
+            result
+                .errors_from(Source::Configured)
+                .count(),
+            0
+        );
+
+        // Excluding everything in a directory skips the directory itself.
+        let options = DirOptions::new()
+            .max_depth(1)
+            .exclude("old/**");
+        let result = load_certs_from_dir(root.join("vendor"), &options);
+        assert!(result.skipped.contains(&Skipped {
+            path: root.join("vendor/old"),
+            reason: SkipReason::Excluded,
+        }));
+
+        let result = load_certs_from_dir(root, &DirOptions::new().max_depth(usize::MAX));
+        assert_eq!(result.errors.len(), 1); // a.pem.bak
+        assert_eq!(
+            result.certs.len(),
+            count(chain) + count(globalsign) + count(same_subject)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let elsewhere = temp_dir.path().join("elsewhere");
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(&elsewhere).unwrap();
+        fs::write(
+            elsewhere.join("ca.pem"),
+            include_bytes!("../integration-tests/one-existing-ca.pem"),
+        )
+        .unwrap();
+        symlink(elsewhere.join("ca.pem"), root.join("file-link.pem")).unwrap();
+        symlink(&elsewhere, root.join("dir-link")).unwrap();
+        symlink(&root, root.join("loop")).unwrap();
+        symlink(root.join("missing"), root.join("dangling")).unwrap();
+
+        let reasons = |policy| {
+            let result = load_certs_from_dir(
+                &root,
+                &DirOptions::new()
+                    .max_depth(5)
+                    .symlinks(policy),
+            );
+            assert!(result.errors.is_empty(), "{:?}", result.errors);
+            let mut reasons = result
+                .skipped
+                .iter()
+                .map(|s| {
+                    (
+                        s.path
+                            .file_name()
+                            .unwrap()
+                            .to_str()
+                            .unwrap()
+                            .to_owned(),
+                        s.reason,
+                    )
+                })
+                .collect::<Vec<_>>();
+            reasons.sort_by(|a, b| a.0.cmp(&b.0));
+            (result.certs.len(), reasons)
+        };
+
+        assert_eq!(
+            reasons(SymlinkPolicy::Files),
+            (
+                1,
+                vec![
+                    ("dangling".to_owned(), SkipReason::DanglingSymlink),
+                    ("dir-link".to_owned(), SkipReason::Symlink),
+                    ("loop".to_owned(), SkipReason::Symlink),
+                ]
+            )
+        );
+        assert_eq!(
+            reasons(SymlinkPolicy::All),
+            (
+                1,
+                vec![
+                    ("dangling".to_owned(), SkipReason::DanglingSymlink),
+                    ("loop".to_owned(), SkipReason::AlreadyScanned),
+                ]
+            )
+        );
+        assert_eq!(
+            reasons(SymlinkPolicy::None),
+            (
+                0,
+                vec![
+                    ("dangling".to_owned(), SkipReason::Symlink),
+                    ("dir-link".to_owned(), SkipReason::Symlink),
+                    ("file-link.pem".to_owned(), SkipReason::Symlink),
+                    ("loop".to_owned(), SkipReason::Symlink),
+                ]
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_containment() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(&shared).unwrap();
+        fs::write(
+            shared.join("ca.pem"),
+            include_bytes!("../integration-tests/one-existing-ca.pem"),
+        )
+        .unwrap();
+        fs::write(
+            root.join("local.pem"),
+            include_bytes!("../tests/badssl-com-chain.pem"),
+        )
+        .unwrap();
+        symlink(shared.join("ca.pem"), root.join("shared.pem")).unwrap();
+        symlink("/etc/passwd", root.join("passwd.pem")).unwrap();
+        // Excluded, so never followed.
+        symlink("/etc/passwd", root.join("passwd.bak")).unwrap();
+
+        let load = |containment| {
+            load_certs_from_dir(
+                &root,
+                &DirOptions::new()
+                    .extension("pem")
+                    .symlink_containment(containment),
+            )
+        };
+        let refused = |result: &CertificateResult| {
+            let mut refused = result
+                .errors
+                .iter()
+                .map(|err| match &err.kind {
+                    ErrorKind::SymlinkNotAllowed { path, target } => (
+                        path.file_name()
+                            .unwrap()
+                            .to_str()
+                            .unwrap()
+                            .to_owned(),
+                        target.clone(),
+                    ),
+                    kind => panic!("unexpected error {kind:?}"),
+                })
+                .collect::<Vec<_>>();
+            refused.sort();
+            refused
+        };
+
+        let result = load(SymlinkContainment::Within(vec![shared.clone()]));
+        assert_eq!(result.certs.len(), 2);
+        assert_eq!(
+            refused(&result),
+            [(
+                "passwd.pem".to_owned(),
+                fs::canonicalize("/etc/passwd").unwrap()
+            )]
+        );
+        assert!(result.errors[0]
+            .to_string()
+            .contains("not allowed"));
+
+        let result = load(SymlinkContainment::Deny);
+        assert_eq!(result.certs.len(), 1);
+        assert_eq!(
+            refused(&result),
+            [
+                ("passwd.pem".to_owned(), PathBuf::from("/etc/passwd")),
+                ("shared.pem".to_owned(), shared.join("ca.pem")),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_swapped_after_check() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let shared = temp_dir.path().join("shared");
+        let elsewhere = temp_dir.path().join("elsewhere");
+        for dir in [&root, &shared, &elsewhere] {
+            fs::create_dir(dir).unwrap();
+        }
+        let globalsign = include_bytes!("../integration-tests/one-existing-ca.pem");
+        fs::write(shared.join("ca.pem"), globalsign).unwrap();
+        fs::write(
+            elsewhere.join("ca.pem"),
+            include_bytes!("../tests/badssl-com-chain.pem"),
+        )
+        .unwrap();
+        symlink(shared.join("ca.pem"), root.join("link.pem")).unwrap();
+
+        // Point the symlink outside `shared` once it has been checked.
+        let link = root.join("link.pem");
+        let target = elsewhere.join("ca.pem");
+        BEFORE_READ.with(|hook| {
+            *hook.borrow_mut() = Some(Box::new(move |path| {
+                assert_eq!(path, link);
+                fs::remove_file(path).unwrap();
+                symlink(&target, path).unwrap();
+            }))
+        });
+        let result = load_certs_from_dir(
+            &root,
+            &DirOptions::new().symlink_containment(SymlinkContainment::Within(vec![shared])),
+        );
+        BEFORE_READ.with(|hook| hook.borrow_mut().take());
+
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let expected = CertificateDer::from_pem_slice(globalsign).unwrap();
+        assert_eq!(result.certs, slice::from_ref(&expected));
+        assert_eq!(
+            result
+                .provenance_of(&expected)
+                .unwrap()
+                .origin,
+            Origin::File {
+                path: root.join("link.pem"),
+                index: 0
+            }
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_swapped_after_check() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let elsewhere = temp_dir.path().join("elsewhere");
+        for dir in [&root, &elsewhere] {
+            fs::create_dir(dir).unwrap();
+        }
+        let globalsign = include_bytes!("../integration-tests/one-existing-ca.pem");
+        fs::write(root.join("ca.pem"), globalsign).unwrap();
+        fs::write(
+            elsewhere.join("ca.pem"),
+            include_bytes!("../tests/badssl-com-chain.pem"),
+        )
+        .unwrap();
+
+        // Replace the regular file with a symlink outside `root` once it has been
+        // checked.
+        let swap = |target: PathBuf| {
+            BEFORE_READ.with(|hook| {
+                *hook.borrow_mut() = Some(Box::new(move |path| {
+                    fs::remove_file(path).unwrap();
+                    symlink(&target, path).unwrap();
+                }))
+            })
+        };
+        swap(elsewhere.join("ca.pem"));
+        let result = load_certs_from_dir(
+            &root,
+            &DirOptions::new().symlink_containment(SymlinkContainment::Deny),
+        );
+        BEFORE_READ.with(|hook| hook.borrow_mut().take());
+
+        assert!(result.certs.is_empty());
+        assert!(matches!(
+            &result.errors[..],
+            [Error {
+                kind: ErrorKind::Io { path, .. },
+                ..
+            }] if *path == root.join("ca.pem")
+        ));
+
+        // Without containment, the symlink is followed as usual.
+        fs::remove_file(root.join("ca.pem")).unwrap();
+        fs::write(root.join("ca.pem"), globalsign).unwrap();
+        swap(elsewhere.join("ca.pem"));
+        let result = load_certs_from_dir(&root, &DirOptions::new());
+        BEFORE_READ.with(|hook| hook.borrow_mut().take());
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(!result.certs.is_empty());
+        assert!(!result
+            .certs
+            .contains(&CertificateDer::from_pem_slice(globalsign).unwrap()));
+    }
+}
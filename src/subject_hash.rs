@@ -0,0 +1,223 @@
+//! OpenSSL's subject name hashes, which name the files in a hashed certificate
+//! directory.
+//!
+//! The current hash is computed over a canonical form of the name, so that names
+//! that differ only in case, whitespace or string type hash the same. See
+//! `x509_name_canon()` in OpenSSL's `crypto/x509/x_name.c`. The old hash, used
+//! before OpenSSL 1.0.0, is computed over the name exactly as encoded.
+
+use md5::Md5;
+use pki_types::CertificateDer;
+use sha1::{Digest, Sha1};
+
+use crate::der::{self, Reader, SEQUENCE};
+use crate::info::{decode_string, UTF8_STRING};
+use crate::x509;
+
+/// The OpenSSL subject name hashes of the certificate `cert`.
+///
+/// Returns `None` if the subject name of `cert` can't be parsed.
+///
+/// A directory for `SSL_CERT_DIR` names each certificate `<hash>.<n>`, where
+/// `<hash>` is one of these hashes formatted as 8 lowercase hexadecimal digits
+/// (`format!("{:08x}", hash)`), and `<n>` numbers certificates with the same hash.
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "hashed-dir")))]
+pub fn subject_hash(cert: &CertificateDer<'_>) -> Option<SubjectHash> {
+    let subject = x509::subject(cert).ok()?;
+    Some(SubjectHash {
+        hash: name_hash(subject).ok()?,
+        old_hash: truncate(&Md5::digest(subject)),
+    })
+}
+
+/// The current OpenSSL hash of the DER-encoded `name`.
+pub(crate) fn name_hash(name: &[u8]) -> Result<u32, der::Error> {
+    Ok(truncate(&Sha1::digest(canonical_name(name)?)))
+}
+
+/// The subject name hashes of a certificate, returned by [`subject_hash()`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(rustls_native_certs_docsrs, doc(cfg(feature = "hashed-dir")))]
+pub struct SubjectHash {
+    /// The hash used by OpenSSL 1.0.0 and later, as printed by
+    /// `openssl x509 -subject_hash`.
+    ///
+    /// This is the SHA-1 digest of the canonical form of the name.
+    pub hash: u32,
+
+    /// The hash used by OpenSSL before 1.0.0, as printed by
+    /// `openssl x509 -subject_hash_old`.
+    ///
+    /// This is the MD5 digest of the DER encoding of the name.
+    pub old_hash: u32,
+}
+
+/// The first four bytes of `digest`, as a little-endian integer.
+fn truncate(digest: &[u8]) -> u32 {
+    u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+/// The canonical encoding of the DER-encoded `name`: each relative distinguished
+/// name, with its string values canonicalized, without the outer SEQUENCE.
+///
+/// Names that OpenSSL considers equal have the same canonical encoding.
+pub(crate) fn canonical_name(name: &[u8]) -> Result<Vec<u8>, der::Error> {
+    let mut out = Vec::new();
+    let mut rdns = Reader::new(Reader::new(name).expect(SEQUENCE)?);
+    while !rdns.is_empty() {
+        let mut attributes = Reader::new(rdns.expect(SET)?);
+        let mut encoded = Vec::new();
+        while !attributes.is_empty() {
+            let mut attribute = Reader::new(attributes.expect(SEQUENCE)?);
+            let oid = attribute.expect_raw(OID)?;
+            let start = attribute.remaining();
+            let (tag, contents) = attribute.read()?;
+            let value = &start[..start.len() - attribute.remaining().len()];
+
+            let mut contents_out = oid.to_vec();
+            match canonical_string(tag, contents) {
+                Some(canonical) => der::write(UTF8_STRING, &canonical, &mut contents_out),
+                None => contents_out.extend_from_slice(value),
+            }
+
+            let mut sequence = Vec::new();
+            der::write(SEQUENCE, &contents_out, &mut sequence);
+            encoded.push(sequence);
+        }
+
+        // DER orders the members of a SET OF by their encoding.
+        encoded.sort();
+        der::write(SET, &encoded.concat(), &mut out);
+    }
+
+    Ok(out)
+}
+
+/// The canonical form of a string value: converted to UTF-8, without leading or
+/// trailing whitespace, with other runs of whitespace replaced by a single space,
+/// and with ASCII letters in lower case.
+///
+/// Returns `None` for values that are not strings, or can't be decoded; these are
+/// left as they are.
+fn canonical_string(tag: u8, contents: &[u8]) -> Option<Vec<u8>> {
+    let text = decode_string(tag, contents)?;
+    let is_space = |b: &u8| matches!(b, b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r');
+    let mut out = Vec::with_capacity(text.len());
+    let mut space = false;
+    for b in text
+        .as_bytes()
+        .iter()
+        .skip_while(|b| is_space(b))
+    {
+        match is_space(b) {
+            true => space = true,
+            false => {
+                if space {
+                    out.push(b' ');
+                    space = false;
+                }
+                out.push(b.to_ascii_lowercase());
+            }
+        }
+    }
+
+    Some(out)
+}
+
+const OID: u8 = 0x06;
+const SET: u8 = 0x31;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pki_types::pem::PemObject;
+
+    use crate::info::{BMP_STRING, PRINTABLE_STRING, T61_STRING, UNIVERSAL_STRING};
+
+    #[test]
+    fn openssl_hashes() {
+        // As printed by `openssl x509 -subject_hash -subject_hash_old`, and as named
+        // by `openssl rehash` and `openssl rehash -compat`. (The certificates in the
+        // deduplication test in lib.rs are stored under made-up names.)
+        for (pem, hash, old_hash) in [
+            (
+                &include_bytes!("../tests/badssl-com-chain.pem")[..],
+                0xc275f070,
+                0x4388eec2,
+            ),
+            (
+                include_bytes!("../integration-tests/one-existing-ca.pem"),
+                0x062cdee6,
+                0x1e8e7201,
+            ),
+            (
+                include_bytes!("../tests/same-subject.pem"),
+                0x2e73ff31,
+                0x5eec7359,
+            ),
+        ] {
+            let cert = CertificateDer::from_pem_slice(pem).unwrap();
+            assert_eq!(subject_hash(&cert), Some(SubjectHash { hash, old_hash }));
+        }
+
+        assert_eq!(subject_hash(&CertificateDer::from(vec![0x30, 0x00])), None);
+    }
+
+    #[test]
+    fn canonical_strings() {
+        let canonical = |tag, contents: &[u8]| {
+            canonical_string(tag, contents).map(|s| String::from_utf8(s).unwrap())
+        };
+        assert_eq!(
+            canonical(PRINTABLE_STRING, b"  Example \t CA  "),
+            Some("example ca".into())
+        );
+        assert_eq!(
+            canonical(UTF8_STRING, "Ünïcode  ÄB".as_bytes()),
+            Some("Ünïcode Äb".into())
+        );
+        assert_eq!(canonical(T61_STRING, b"\xe9T"), Some("ét".into()));
+        assert_eq!(
+            canonical(BMP_STRING, &[0, b'A', 0, b' ', 0, b'B']),
+            Some("a b".into())
+        );
+        assert_eq!(canonical(BMP_STRING, &[0, b'A', 0]), None);
+        assert_eq!(
+            canonical(UNIVERSAL_STRING, &[0, 0, 0, b'X']),
+            Some("x".into())
+        );
+        assert_eq!(canonical(0x04, b"X"), None);
+    }
+
+    #[test]
+    fn equivalent_names() {
+        let name = |rdns: &[(u8, &[u8])]| {
+            let mut contents = Vec::new();
+            for (tag, value) in rdns {
+                let mut attribute = vec![0x06, 0x03, 0x55, 0x04, 0x03];
+                der::write(*tag, value, &mut attribute);
+                let mut sequence = Vec::new();
+                der::write(SEQUENCE, &attribute, &mut sequence);
+                der::write(SET, &sequence, &mut contents);
+            }
+            let mut out = Vec::new();
+            der::write(SEQUENCE, &contents, &mut out);
+            canonical_name(&out).unwrap()
+        };
+
+        assert_eq!(
+            name(&[(PRINTABLE_STRING, b"Example CA")]),
+            name(&[(UTF8_STRING, b" example  ca")])
+        );
+        assert_ne!(
+            name(&[(PRINTABLE_STRING, b"Example CA")]),
+            name(&[(PRINTABLE_STRING, b"Example CA 2")])
+        );
+        assert!(canonical_name(&[0x30, 0x02, 0x31, 0x00])
+            .unwrap()
+            .starts_with(&[SET]));
+        assert!(canonical_name(&[0x30, 0x02, 0x30, 0x00]).is_err());
+    }
+}
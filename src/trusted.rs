@@ -0,0 +1,119 @@
+//! OpenSSL `TRUSTED CERTIFICATE` PEM blocks.
+//!
+//! These contain a DER-encoded certificate followed by OpenSSL's `X509_CERT_AUX`
+//! structure, which lists the extended key usages the certificate is trusted and
+//! rejected for:
+//!
+//! ```text
+//! X509_CERT_AUX ::= SEQUENCE {
+//!     trust       SEQUENCE OF OBJECT IDENTIFIER OPTIONAL,
+//!     reject  [0] IMPLICIT SEQUENCE OF OBJECT IDENTIFIER OPTIONAL,
+//!     alias       UTF8String OPTIONAL,
+//!     keyid       OCTET STRING OPTIONAL,
+//!     other   [1] IMPLICIT SEQUENCE OF AlgorithmIdentifier OPTIONAL
+//! }
+//! ```
+
+use crate::der::{self, Reader, SEQUENCE};
+use crate::x509;
+
+/// Split the contents of a `TRUSTED CERTIFICATE` block into the certificate and
+/// its auxiliary trust settings.
+pub(crate) fn split(data: &[u8]) -> Result<(&[u8], Aux<'_>), der::Error> {
+    let mut reader = Reader::new(data);
+    Reader::new(reader.expect(SEQUENCE)?).expect(SEQUENCE)?; // tbsCertificate
+    let cert = &data[..data.len() - reader.remaining().len()];
+
+    let mut aux = Aux::default();
+    if reader.is_empty() {
+        return Ok((cert, aux));
+    }
+
+    let mut fields = Reader::new(reader.expect(SEQUENCE)?);
+    if !reader.is_empty() {
+        return Err(der::Error);
+    }
+
+    while !fields.is_empty() {
+        match fields.read()? {
+            (SEQUENCE, oids) => aux.trust = object_identifiers(oids)?,
+            (CONTEXT_0, oids) => aux.reject = object_identifiers(oids)?,
+            _ => {}
+        }
+    }
+
+    Ok((cert, aux))
+}
+
+fn object_identifiers(data: &[u8]) -> Result<Vec<&[u8]>, der::Error> {
+    let mut reader = Reader::new(data);
+    let mut oids = Vec::new();
+    while !reader.is_empty() {
+        oids.push(reader.expect(OID)?);
+    }
+    Ok(oids)
+}
+
+/// The trust settings from an `X509_CERT_AUX`.
+#[derive(Debug, Default)]
+pub(crate) struct Aux<'a> {
+    trust: Vec<&'a [u8]>,
+    reject: Vec<&'a [u8]>,
+}
+
+impl Aux<'_> {
+    /// Whether the certificate may be used to authenticate TLS servers.
+    ///
+    /// This is the case unless `serverAuth` (or `anyExtendedKeyUsage`) is rejected,
+    /// or trusted purposes are listed but `serverAuth` is not one of them.
+    pub(crate) fn trusted_for_server_auth(&self) -> bool {
+        let server_auth = |oid: &&[u8]| x509::permits_server_auth(oid);
+        !self.reject.iter().any(server_auth)
+            && (self.trust.is_empty() || self.trust.iter().any(server_auth))
+    }
+}
+
+const OID: u8 = 0x06;
+const CONTEXT_0: u8 = 0xa0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trust_settings() {
+        let cert = [0x30, 0x02, 0x30, 0x00];
+        let with_aux = |aux: &[u8]| [&cert[..], aux].concat();
+        let trusted = |data: &[u8]| {
+            let (c, aux) = split(data).unwrap();
+            assert_eq!(c, cert);
+            aux.trusted_for_server_auth()
+        };
+
+        // No auxiliary data at all
+        assert!(trusted(&cert));
+        // Empty X509_CERT_AUX
+        assert!(trusted(&with_aux(&[0x30, 0x00])));
+        // trust: serverAuth
+        assert!(trusted(&with_aux(&[
+            0x30, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01
+        ])));
+        // trust: emailProtection
+        assert!(!trusted(&with_aux(&[
+            0x30, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x04
+        ])));
+        // reject: anyExtendedKeyUsage; alias: "x"
+        assert!(!trusted(&with_aux(&[
+            0x30, 0x0b, 0xa0, 0x06, 0x06, 0x04, 0x55, 0x1d, 0x25, 0x00, 0x0c, 0x01, 0x78
+        ])));
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(split(&[]).is_err());
+        assert!(split(&[0x30, 0x00]).is_err());
+        assert!(split(&[0x30, 0x02, 0x30, 0x00, 0x30]).is_err());
+        assert!(split(&[0x30, 0x02, 0x30, 0x00, 0x30, 0x00, 0x30, 0x00]).is_err());
+        assert!(split(&[0x30, 0x02, 0x30, 0x00, 0x30, 0x02, 0x30, 0x01]).is_err());
+    }
+}
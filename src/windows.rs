@@ -2,7 +2,7 @@ use pki_types::CertificateDer;
 use schannel::cert_context::ValidUses;
 use schannel::cert_store::CertStore;
 
-use super::CertificateResult;
+use super::{CertificateResult, Origin};
 
 pub fn load_native_certs() -> CertificateResult {
     let mut result = CertificateResult::default();
@@ -16,9 +16,12 @@ pub fn load_native_certs() -> CertificateResult {
 
     for cert in current_user_store.certs() {
         if usable_for_rustls(cert.valid_uses().unwrap()) && cert.is_time_valid().unwrap() {
-            result
-                .certs
-                .push(CertificateDer::from(cert.to_der().to_vec()));
+            result.push_cert(
+                CertificateDer::from(cert.to_der().to_vec()),
+                Origin::Platform {
+                    store: "CurrentUser\\ROOT",
+                },
+            );
         }
     }
 
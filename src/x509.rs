@@ -0,0 +1,275 @@
+//! Just enough of X.509 certificates, as described in
+//! [RFC 5280](https://www.rfc-editor.org/rfc/rfc5280), to apply trust policy.
+
+use std::time::{Duration, SystemTime};
+
+use crate::der::{self, Reader, SEQUENCE};
+
+/// The DER encoding of the `SubjectPublicKeyInfo` of the certificate `cert`.
+pub(crate) fn subject_public_key_info(cert: &[u8]) -> Result<&[u8], der::Error> {
+    let mut tbs = tbs_certificate(cert)?;
+    tbs.expect(SEQUENCE)?; // subject
+    tbs.expect_raw(SEQUENCE)
+}
+
+/// The DER encoding of the subject name of the certificate `cert`.
+pub(crate) fn subject(cert: &[u8]) -> Result<&[u8], der::Error> {
+    tbs_certificate(cert)?.expect_raw(SEQUENCE)
+}
+
+/// The DER encoding of the issuer name of the certificate `cert`.
+pub(crate) fn issuer(cert: &[u8]) -> Result<&[u8], der::Error> {
+    tbs_certificate_at_issuer(cert)?.expect_raw(SEQUENCE)
+}
+
+/// The `notBefore` and `notAfter` times of the certificate `cert`.
+pub(crate) fn validity(cert: &[u8]) -> Result<(SystemTime, SystemTime), der::Error> {
+    let mut tbs = tbs_certificate_at_issuer(cert)?;
+    tbs.expect(SEQUENCE)?; // issuer
+    let mut validity = Reader::new(tbs.expect(SEQUENCE)?);
+    let mut time = || match validity.read()? {
+        (UTC_TIME | GENERALIZED_TIME, value) => parse_time(value).ok_or(der::Error),
+        _ => Err(der::Error),
+    };
+    Ok((time()?, time()?))
+}
+
+/// An extension of a certificate.
+pub(crate) struct Extension<'a> {
+    /// The contents of the extension's `extnID`.
+    pub(crate) oid: &'a [u8],
+    /// The contents of the extension's `extnValue`: the DER encoding of the value.
+    pub(crate) value: &'a [u8],
+}
+
+/// The extensions of the certificate `cert`.
+pub(crate) fn extensions(cert: &[u8]) -> Result<Vec<Extension<'_>>, der::Error> {
+    let mut tbs = tbs_certificate(cert)?;
+    tbs.expect(SEQUENCE)?; // subject
+    tbs.expect(SEQUENCE)?; // subjectPublicKeyInfo
+    for tag in [CONTEXT_1, CONTEXT_2] {
+        if tbs.remaining().first() == Some(&tag) {
+            tbs.read()?; // issuerUniqueID, subjectUniqueID
+        }
+    }
+
+    let mut extensions = Vec::new();
+    if tbs.is_empty() {
+        return Ok(extensions);
+    }
+
+    let mut explicit = Reader::new(tbs.expect(CONTEXT_3)?);
+    let mut reader = Reader::new(explicit.expect(SEQUENCE)?);
+    while !reader.is_empty() {
+        let mut extension = Reader::new(reader.expect(SEQUENCE)?);
+        let oid = extension.expect(OID)?;
+        if extension.remaining().first() == Some(&BOOLEAN) {
+            extension.read()?; // critical
+        }
+        extensions.push(Extension {
+            oid,
+            value: extension.expect(OCTET_STRING)?,
+        });
+    }
+
+    Ok(extensions)
+}
+
+/// The purposes listed in the extended key usage extension of the certificate
+/// `cert`, or `None` if it has no such extension.
+pub(crate) fn extended_key_usage(cert: &[u8]) -> Result<Option<Vec<&[u8]>>, der::Error> {
+    let Some(extension) = extensions(cert)?
+        .into_iter()
+        .find(|extension| extension.oid == EXTENDED_KEY_USAGE)
+    else {
+        return Ok(None);
+    };
+
+    let mut value = Reader::new(extension.value);
+    let mut oids = Reader::new(value.expect(SEQUENCE)?);
+    let mut purposes = Vec::new();
+    while !oids.is_empty() {
+        purposes.push(oids.expect(OID)?);
+    }
+    Ok(Some(purposes))
+}
+
+/// A reader for the `tbsCertificate` of `cert`, positioned at the subject.
+fn tbs_certificate(cert: &[u8]) -> Result<Reader<'_>, der::Error> {
+    let mut tbs = tbs_certificate_at_issuer(cert)?;
+    tbs.expect(SEQUENCE)?; // issuer
+    tbs.expect(SEQUENCE)?; // validity
+    Ok(tbs)
+}
+
+/// A reader for the `tbsCertificate` of `cert`, positioned at the issuer.
+fn tbs_certificate_at_issuer(cert: &[u8]) -> Result<Reader<'_>, der::Error> {
+    let mut outer = Reader::new(cert);
+    let mut certificate = Reader::new(outer.expect(SEQUENCE)?);
+    let mut tbs = Reader::new(certificate.expect(SEQUENCE)?);
+    if tbs.remaining().first() == Some(&CONTEXT_0) {
+        tbs.read()?; // version
+    }
+
+    tbs.expect(INTEGER)?; // serialNumber
+    tbs.expect(SEQUENCE)?; // signature
+    Ok(tbs)
+}
+
+/// Whether the extended key usage `oid` covers TLS server authentication.
+pub(crate) fn permits_server_auth(oid: &[u8]) -> bool {
+    oid == SERVER_AUTH || oid == ANY_EXTENDED_KEY_USAGE
+}
+
+/// Parse a `UTCTime` (`YYMMDDHHMMSSZ`) or `GeneralizedTime` (`YYYYMMDDHHMMSSZ`).
+///
+/// As required by RFC 5280, only UTC times with seconds and without fractions
+/// are supported.
+pub(crate) fn parse_time(value: &[u8]) -> Option<SystemTime> {
+    let (year, rest) = match value.len() {
+        13 => match digits(&value[..2])? {
+            year @ 0..=49 => (2000 + year, &value[2..]),
+            year => (1900 + year, &value[2..]),
+        },
+        15 => (digits(&value[..4])?, &value[4..]),
+        _ => return None,
+    };
+
+    if rest[10] != b'Z' {
+        return None;
+    }
+
+    let (month, day) = (digits(&rest[0..2])?, digits(&rest[2..4])?);
+    let (hour, minute, second) = (
+        digits(&rest[4..6])?,
+        digits(&rest[6..8])?,
+        digits(&rest[8..10])?,
+    );
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=days_in_month).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    // Days since 1970-01-01, from Howard Hinnant's `days_from_civil`.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146_097 + doe).checked_sub(719_468)?;
+
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+fn digits(value: &[u8]) -> Option<u64> {
+    value
+        .iter()
+        .try_fold(0, |acc, &b| match b {
+            b'0'..=b'9' => Some(acc * 10 + u64::from(b - b'0')),
+            _ => None,
+        })
+}
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const CONTEXT_1: u8 = 0x81;
+const CONTEXT_2: u8 = 0x82;
+const CONTEXT_0: u8 = 0xa0;
+const CONTEXT_3: u8 = 0xa3;
+
+/// 2.5.29.37
+const EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+/// 1.3.6.1.5.5.7.3.1
+const SERVER_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+/// 2.5.29.37.0
+const ANY_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25, 0x00];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pki_types::pem::PemObject;
+    use pki_types::CertificateDer;
+
+    #[test]
+    fn spki() {
+        let cert = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        let spki = subject_public_key_info(&cert).unwrap();
+        // RSA 2048 public key
+        assert_eq!(spki.len(), 294);
+        assert_eq!(&spki[..4], &[0x30, 0x82, 0x01, 0x22]);
+        assert!(subject_public_key_info(&cert[..cert.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn validity_and_extensions() {
+        let cert = CertificateDer::from_pem_slice(include_bytes!(
+            "../integration-tests/one-existing-ca.pem"
+        ))
+        .unwrap();
+        let secs = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        // 2009-03-18 10:00:00 UTC to 2029-03-18 10:00:00 UTC
+        let (not_before, not_after) = validity(&cert).unwrap();
+        assert_eq!(
+            (secs(not_before), secs(not_after)),
+            (1_237_370_400, 1_868_522_400)
+        );
+
+        // keyUsage, basicConstraints, subjectKeyIdentifier
+        let extensions = extensions(&cert).unwrap();
+        assert_eq!(
+            extensions
+                .iter()
+                .map(|ext| ext.oid)
+                .collect::<Vec<_>>(),
+            [
+                &[0x55, 0x1d, 0x0f][..],
+                &[0x55, 0x1d, 0x13],
+                &[0x55, 0x1d, 0x0e]
+            ]
+        );
+        assert_eq!(extensions[1].value, [0x30, 0x03, 0x01, 0x01, 0xff]);
+    }
+
+    #[test]
+    fn times() {
+        let secs = |value: &[u8]| {
+            parse_time(value).map(|time| {
+                time.duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            })
+        };
+
+        assert_eq!(secs(b"700101000000Z"), Some(0));
+        assert_eq!(secs(b"19700101000000Z"), Some(0));
+        assert_eq!(secs(b"241130235959Z"), Some(1_733_011_199));
+        assert_eq!(secs(b"20000229120000Z"), Some(951_825_600));
+        assert_eq!(secs(b"490101000000Z"), Some(2_493_072_000));
+        assert_eq!(secs(b"19000228000000Z"), None);
+        assert_eq!(secs(b"19000229000000Z"), None);
+        assert_eq!(secs(b"241130235959+0100"), None);
+        assert_eq!(secs(b"2411302359Z"), None);
+        assert_eq!(secs(b"241131000000Z"), None);
+    }
+}
@@ -10,7 +10,8 @@ use std::env;
 /// `[env::remove_var()]` is unsafe if another thread is running.
 ///
 /// Note that `env::remove_var()` is scheduled to become unsafe in Rust
-/// Edition 2024.
+/// Edition 2024. Tests that don't need the process environment should use
+/// `Loader::environment()` instead, which can run in parallel.
 pub(crate) unsafe fn clear_env() {
     env::remove_var("SSL_CERT_FILE");
     env::remove_var("SSL_CERT_DIR");